@@ -0,0 +1,56 @@
+//! Builds a synthetic 500 file site with the `marmite` binary.
+//!
+//! Run with `cargo bench`.
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const FILES: usize = 500;
+
+fn write_corpus(input: &Path) {
+    let content = input.join("content");
+    fs::create_dir_all(&content).unwrap();
+    fs::write(input.join("marmite.yaml"), "name: Bench\n").unwrap();
+    for i in 0..FILES {
+        let body = format!(
+            "---\ntitle: Post {i}\ntags: bench, tag-{}\n---\n\n# Post {i}\n\n{}",
+            i % 20,
+            "Some *markdown* text with a [link](./post-0.html) and `code`.\n\n".repeat(20)
+        );
+        fs::write(
+            content.join(format!("2024-01-{:02}-post-{i}.md", i % 28 + 1)),
+            body,
+        )
+        .unwrap();
+    }
+}
+
+fn build(c: &mut Criterion) {
+    let root: PathBuf = std::env::temp_dir().join("marmite-bench");
+    let input = root.join("input");
+    let output = root.join("output");
+    let _ = fs::remove_dir_all(&root);
+    write_corpus(&input);
+
+    c.bench_function("build 500 files", |b| {
+        b.iter(|| {
+            let status = Command::new(env!("CARGO_BIN_EXE_marmite"))
+                .arg(&input)
+                .arg(&output)
+                .env("RUST_LOG", "error")
+                .status()
+                .unwrap();
+            assert!(status.success());
+        });
+    });
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = build
+}
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+//! Builds the `example/` site with and without `minify_html`, printing the
+//! total size of the generated HTML for both.
+//!
+//! Run with `cargo bench --bench minify`.
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use walkdir::WalkDir;
+
+fn build_example(output: &Path, minify: bool) {
+    let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("example");
+    let mut config = fs::read_to_string(example.join("marmite.yaml")).unwrap();
+    config.push_str(&format!("\nminify_html: {minify}\n"));
+    let config_path = output.with_extension("yaml");
+    fs::write(&config_path, config).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_marmite"))
+        .arg(&example)
+        .arg(output)
+        .arg("--config")
+        .arg(&config_path)
+        .arg("--no-cache")
+        .env("RUST_LOG", "error")
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+fn html_size(output: &Path) -> u64 {
+    WalkDir::new(output)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "html"))
+        .map(|e| e.metadata().unwrap().len())
+        .sum()
+}
+
+fn minify(c: &mut Criterion) {
+    let root: PathBuf = std::env::temp_dir().join("marmite-bench-minify");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let plain = root.join("plain");
+    let minified = root.join("minified");
+
+    build_example(&plain, false);
+    build_example(&minified, true);
+    let (before, after) = (html_size(&plain), html_size(&minified));
+    println!(
+        "HTML size: {before} bytes, minified: {after} bytes ({:.1}% smaller)",
+        100.0 - after as f64 * 100.0 / before as f64
+    );
+
+    let mut group = c.benchmark_group("example");
+    group.sample_size(10);
+    group.bench_function("build", |b| b.iter(|| build_example(&plain, false)));
+    group.bench_function("build minified", |b| {
+        b.iter(|| build_example(&minified, true));
+    });
+    group.finish();
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, minify);
+criterion_main!(benches);
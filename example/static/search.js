@@ -10,7 +10,8 @@ import Fuse from "https://cdnjs.cloudflare.com/ajax/libs/fuse.js/7.0.0/fuse.basi
     };
 
     try {
-        const response = await fetch('./static/search_index.json');
+        // relative to this script, so it also works from language subdirectories
+        const response = await fetch(new URL('search_index.json', import.meta.url));
         const data = await response.json();
         const fuse = new Fuse(data, fuseOptions);
         document.getElementById("marmite-search-input").addEventListener("input", (event) => {
@@ -32,7 +33,7 @@ import Fuse from "https://cdnjs.cloudflare.com/ajax/libs/fuse.js/7.0.0/fuse.basi
                     results.slice(0, 10).forEach((result) => {
                         const elementList = document.createElement("li");
                         const resultElement = document.createElement("a");
-                        resultElement.href = `${result.item.slug}.html`;
+                        resultElement.href = new URL(`../${result.item.slug}.html`, import.meta.url);
                         resultElement.innerText = result.item.title;
                         elementList.appendChild(resultElement);
                         resultsElement.appendChild(elementList);
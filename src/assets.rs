@@ -0,0 +1,202 @@
+use log::{debug, error, info};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Copies every CSS and JS file in `static_dir` to `<stem>-<hash>.<ext>`,
+/// returning the mapping from the original to the new path, both relative
+/// to `static_dir` (e.g. `marmite.css` -> `marmite-1a2b3c4d.css`). The
+/// originals are kept, so the next build can skip copying them again, and
+/// the copies of their previous versions are removed.
+pub fn fingerprint_assets(static_dir: &Path) -> HashMap<String, String> {
+    let mut asset_map = HashMap::new();
+    let assets = WalkDir::new(static_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| {
+            e.path().is_file()
+                && e.path()
+                    .extension()
+                    .is_some_and(|ext| ext == "css" || ext == "js")
+        })
+        .map(walkdir::DirEntry::into_path)
+        .collect::<Vec<_>>();
+
+    for asset in assets {
+        let (Some(stem), Some(ext)) = (
+            asset.file_stem().and_then(|s| s.to_str()),
+            asset.extension().and_then(|e| e.to_str()),
+        ) else {
+            continue;
+        };
+        // copies written by a previous build are left as they are
+        if is_fingerprinted(&asset) {
+            continue;
+        }
+        let Ok(bytes) = fs::read(&asset) else {
+            continue;
+        };
+        let hash = format!("{:x}", Sha256::digest(&bytes));
+        let renamed = asset.with_file_name(format!("{stem}-{}.{ext}", &hash[..8]));
+        if !renamed.exists() {
+            if let Err(e) = fs::write(&renamed, &bytes) {
+                error!("Failed to write {}: {}", renamed.display(), e);
+                continue;
+            }
+        }
+        remove_previous_versions(&asset, &renamed);
+        let relative = |path: &Path| {
+            path.strip_prefix(static_dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        asset_map.insert(relative(&asset), relative(&renamed));
+    }
+    debug!("Fingerprinted {} assets", asset_map.len());
+    asset_map
+}
+
+/// Removes the fingerprinted copies of `asset` other than `current`
+fn remove_previous_versions(asset: &Path, current: &Path) {
+    let (Some(stem), Some(ext), Some(dir)) = (
+        asset.file_stem().and_then(|s| s.to_str()),
+        asset.extension().and_then(|e| e.to_str()),
+        asset.parent(),
+    ) else {
+        return;
+    };
+    let previous = Regex::new(&format!(
+        r"^{}-[0-9a-f]{{8}}\.{}$",
+        regex::escape(stem),
+        regex::escape(ext)
+    ))
+    .unwrap();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path != current && previous.is_match(&entry.file_name().to_string_lossy()) {
+            if let Err(e) = fs::remove_file(&path) {
+                debug!("Unable to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Whether the file name ends with the `-<hash>` added by `fingerprint_assets`
+pub fn is_fingerprinted(path: &Path) -> bool {
+    let fingerprinted = Regex::new(r"-[0-9a-f]{8}$").unwrap();
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| fingerprinted.is_match(stem))
+}
+
+/// Points the `src` and `href` attributes linking `<static_path>/<asset>` in
+/// the generated HTML to the fingerprinted names. Text, like file names
+/// quoted in code blocks, is left as written.
+pub fn rewrite_asset_references(
+    output_dir: &Path,
+    static_path: &str,
+    asset_map: &HashMap<String, String>,
+) {
+    if asset_map.is_empty() {
+        return;
+    }
+    let attribute = Regex::new(r#"\b((?:src|href)\s*=\s*)(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    let mut rewritten = 0;
+    let pages = WalkDir::new(output_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().is_file() && e.path().extension().is_some_and(|ext| ext == "html"));
+    for page in pages {
+        let Ok(html) = fs::read_to_string(page.path()) else {
+            continue;
+        };
+        let new_html = attribute.replace_all(&html, |caps: &regex::Captures| {
+            let (quote, url) = match caps.get(2) {
+                Some(url) => ('"', url.as_str()),
+                None => ('\'', caps.get(3).map_or("", |url| url.as_str())),
+            };
+            let url = rewrite_asset_url(url, static_path, asset_map);
+            format!("{}{quote}{url}{quote}", &caps[1])
+        });
+        if new_html != html {
+            if let Err(e) = fs::write(page.path(), new_html.as_bytes()) {
+                error!("Failed to write {}: {}", page.path().display(), e);
+            } else {
+                rewritten += 1;
+            }
+        }
+    }
+    info!("Rewrote asset references in {} pages", rewritten);
+}
+
+/// `url` pointing to the fingerprinted name when its path, before any query
+/// or fragment, ends with `<static_path>/<asset>`
+fn rewrite_asset_url(url: &str, static_path: &str, asset_map: &HashMap<String, String>) -> String {
+    let end = url.find(['?', '#']).unwrap_or(url.len());
+    let (path, suffix) = url.split_at(end);
+    for (original, renamed) in asset_map {
+        let asset = format!("{static_path}/{original}");
+        if let Some(prefix) = path.strip_suffix(&asset) {
+            if prefix.is_empty() || prefix.ends_with('/') {
+                return format!("{prefix}{static_path}/{renamed}{suffix}");
+            }
+        }
+    }
+    url.to_string()
+}
+
+pub fn is_sass(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "scss" || ext == "sass")
+}
+
+/// Compiles `.scss` and `.sass` files from `source_dir` to `.css` at the
+/// same relative path in `output_dir`. Partials (`_name.scss`) are only
+/// compiled through the files importing them. Returns the compile errors.
+pub fn compile_sass(source_dir: &Path, output_dir: &Path) -> Vec<String> {
+    let mut errors = Vec::new();
+    let entrypoints = WalkDir::new(source_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| {
+            e.path().is_file()
+                && is_sass(e.path())
+                && !e.file_name().to_string_lossy().starts_with('_')
+        });
+    for entry in entrypoints {
+        let relative = entry
+            .path()
+            .strip_prefix(source_dir)
+            .unwrap_or(entry.path());
+        let css_path = output_dir.join(relative).with_extension("css");
+        // grass errors already include the line and column
+        let result = grass::from_path(entry.path(), &grass::Options::default())
+            .map_err(|e| e.to_string())
+            .and_then(|css| {
+                if let Some(parent) = css_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                fs::write(&css_path, css).map_err(|e| e.to_string())
+            });
+        match result {
+            Ok(()) => debug!(
+                "Compiled {} to {}",
+                entry.path().display(),
+                css_path.display()
+            ),
+            Err(e) => errors.push(format!(
+                "Failed to compile {}: {}",
+                entry.path().display(),
+                e
+            )),
+        }
+    }
+    errors
+}
@@ -0,0 +1,396 @@
+use crate::cli::Cli;
+use crate::markdown::strip_html_tags;
+use crate::site::{self, Data};
+use log::{error, info};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+/// The checks of `--audit`: id, WCAG success criterion, description and the
+/// W3C page explaining the criterion
+const RULES: [(&str, &str, &str, &str); 6] = [
+    (
+        "image-alt",
+        "1.1.1 Non-text Content",
+        "Images must have an alt attribute",
+        "https://www.w3.org/WAI/WCAG21/Understanding/non-text-content",
+    ),
+    (
+        "input-label",
+        "4.1.2 Name, Role, Value",
+        "Form inputs must have a label",
+        "https://www.w3.org/WAI/WCAG21/Understanding/name-role-value",
+    ),
+    (
+        "link-name",
+        "2.4.4 Link Purpose (In Context)",
+        "Links must have a text",
+        "https://www.w3.org/WAI/WCAG21/Understanding/link-purpose-in-context",
+    ),
+    (
+        "html-lang",
+        "3.1.1 Language of Page",
+        "The <html> element must have a lang attribute",
+        "https://www.w3.org/WAI/WCAG21/Understanding/language-of-page",
+    ),
+    (
+        "page-has-one-h1",
+        "1.3.1 Info and Relationships",
+        "Pages must have exactly one <h1>",
+        "https://www.w3.org/WAI/WCAG21/Understanding/info-and-relationships",
+    ),
+    (
+        "heading-order",
+        "1.3.1 Info and Relationships",
+        "Heading levels must only increase by one",
+        "https://www.w3.org/WAI/WCAG21/Understanding/info-and-relationships",
+    ),
+];
+
+#[derive(Debug, Serialize)]
+struct Issue {
+    rule: &'static str,
+    wcag: &'static str,
+    file: PathBuf,
+    line: usize,
+    element: String,
+    message: String,
+}
+
+/// Builds the site and checks every generated HTML file for common
+/// accessibility issues (`--audit`), printed as a list, JSON or SARIF for
+/// GitHub Code Scanning. Exits with an error when there are issues, unless
+/// `--exit-zero`.
+pub fn audit(
+    config_path: &Path,
+    input_folder: &Path,
+    output_folder: &Arc<PathBuf>,
+    cli_args: &Arc<Cli>,
+) {
+    site::generate(
+        config_path,
+        input_folder,
+        output_folder,
+        false,
+        false,
+        &cli_args.bind,
+        cli_args,
+    );
+    let site_path = Data::load(config_path, cli_args).site.site_path;
+    let output_dir = output_folder.join(site_path);
+
+    let mut issues = Vec::new();
+    let files = WalkDir::new(&output_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().is_file() && e.path().extension().is_some_and(|ext| ext == "html"))
+        .map(walkdir::DirEntry::into_path);
+    for file in files {
+        match fs::read_to_string(&file) {
+            Ok(html) => issues.extend(audit_html(&file, &html)),
+            Err(e) => error!("Unable to read {}: {}", file.display(), e),
+        }
+    }
+
+    match cli_args.format.as_str() {
+        "sarif" => println!(
+            "{}",
+            serde_json::to_string_pretty(&sarif(&issues)).unwrap_or_default()
+        ),
+        "json" => println!(
+            "{}",
+            serde_json::to_string_pretty(&issues).unwrap_or_default()
+        ),
+        _ => {
+            for issue in &issues {
+                println!(
+                    "{}:{}: {} [{}, WCAG {}]\n    {}",
+                    issue.file.display(),
+                    issue.line,
+                    issue.message,
+                    issue.rule,
+                    issue.wcag,
+                    issue.element
+                );
+            }
+        }
+    }
+
+    if issues.is_empty() {
+        info!("No accessibility issues found in {}", output_dir.display());
+    } else if cli_args.exit_zero {
+        info!("Found {} accessibility issues", issues.len());
+    } else {
+        error!("Found {} accessibility issues", issues.len());
+        process::exit(1);
+    }
+}
+
+/// Runs every check of `RULES` on one page, except the redirect pages of
+/// `redirect_from` that browsers leave at once
+fn audit_html(file: &Path, html: &str) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let refresh = Regex::new(r#"(?i)<meta\b[^>]*\shttp-equiv="refresh""#).unwrap();
+    if refresh.is_match(html) {
+        return issues;
+    }
+    let mut report = |rule: &'static str, offset: usize, element: &str, message: String| {
+        let wcag = RULES
+            .iter()
+            .find(|(id, ..)| *id == rule)
+            .map_or("", |(_, wcag, ..)| *wcag);
+        issues.push(Issue {
+            rule,
+            wcag,
+            file: file.to_path_buf(),
+            line: html[..offset].matches('\n').count() + 1,
+            element: snippet(element),
+            message,
+        });
+    };
+
+    let html_tag = Regex::new(r"<html\b[^>]*>").unwrap();
+    match html_tag.find(html) {
+        Some(tag) if !has_attribute(tag.as_str(), "lang") => report(
+            "html-lang",
+            tag.start(),
+            tag.as_str(),
+            "<html> has no lang attribute".to_string(),
+        ),
+        _ => {}
+    }
+
+    let img = Regex::new(r"<img\b[^>]*>").unwrap();
+    for tag in img.find_iter(html) {
+        if !has_attribute(tag.as_str(), "alt") {
+            report(
+                "image-alt",
+                tag.start(),
+                tag.as_str(),
+                "Image has no alt attribute".to_string(),
+            );
+        }
+    }
+
+    let label_for = Regex::new(r#"<label\b[^>]*\sfor="([^"]+)""#).unwrap();
+    let labelled_ids = label_for
+        .captures_iter(html)
+        .map(|cap| cap[1].to_string())
+        .collect::<HashSet<_>>();
+    let label = Regex::new(r"(?s)<label\b.*?</label>").unwrap();
+    let label_spans = label.find_iter(html).map(|m| m.range()).collect::<Vec<_>>();
+    let input = Regex::new(r"<(input|select|textarea)\b[^>]*>").unwrap();
+    let input_type = Regex::new(r#"\stype="([^"]*)""#).unwrap();
+    let id = Regex::new(r#"\sid="([^"]+)""#).unwrap();
+    for tag in input.find_iter(html) {
+        let element = tag.as_str();
+        let kind = input_type
+            .captures(element)
+            .map_or(String::new(), |cap| cap[1].to_lowercase());
+        let labelled = matches!(
+            kind.as_str(),
+            "hidden" | "submit" | "button" | "reset" | "image"
+        ) || ["aria-label", "aria-labelledby", "title"]
+            .iter()
+            .any(|name| has_attribute(element, name))
+            || id
+                .captures(element)
+                .is_some_and(|cap| labelled_ids.contains(&cap[1]))
+            || label_spans.iter().any(|span| span.contains(&tag.start()));
+        if !labelled {
+            report(
+                "input-label",
+                tag.start(),
+                element,
+                "Form input has no label".to_string(),
+            );
+        }
+    }
+
+    let link = Regex::new(r"(?s)<a\b([^>]*)>(.*?)</a>").unwrap();
+    let img_alt = Regex::new(r#"<img\b[^>]*\salt="[^"]*\S[^"]*""#).unwrap();
+    for cap in link.captures_iter(html) {
+        let attributes = &cap[1];
+        // hidden from assistive technologies, like the heading anchors
+        if attributes.contains(r#"aria-hidden="true""#) {
+            continue;
+        }
+        let has_name = !strip_html_tags(&cap[2]).trim().is_empty()
+            || has_attribute(attributes, "aria-label")
+            || has_attribute(attributes, "title")
+            || img_alt.is_match(&cap[2]);
+        if !has_name {
+            let whole = cap.get(0).unwrap();
+            report(
+                "link-name",
+                whole.start(),
+                whole.as_str(),
+                "Link has no text".to_string(),
+            );
+        }
+    }
+
+    let heading = Regex::new(r"<h([1-6])\b[^>]*>").unwrap();
+    let headings = heading
+        .captures_iter(html)
+        .map(|cap| (cap.get(0).unwrap(), cap[1].parse::<usize>().unwrap_or(1)))
+        .collect::<Vec<_>>();
+    let h1_count = headings.iter().filter(|(_, level)| *level == 1).count();
+    if h1_count != 1 {
+        let (offset, element) = headings
+            .iter()
+            .find(|(_, level)| *level == 1)
+            .map_or((0, ""), |(tag, _)| (tag.start(), tag.as_str()));
+        report(
+            "page-has-one-h1",
+            offset,
+            element,
+            format!("Page has {h1_count} <h1> instead of one"),
+        );
+    }
+    for pair in headings.windows(2) {
+        let ((_, previous), (tag, level)) = (pair[0], pair[1]);
+        if level > previous + 1 {
+            report(
+                "heading-order",
+                tag.start(),
+                tag.as_str(),
+                format!("<h{level}> follows <h{previous}>, skipping a level"),
+            );
+        }
+    }
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+/// Whether the start tag has the attribute, with or without a value
+fn has_attribute(tag: &str, name: &str) -> bool {
+    Regex::new(&format!(r"\s{}(\s*=|[\s/>]|$)", regex::escape(name)))
+        .unwrap()
+        .is_match(tag)
+}
+
+/// The element on a single line, cut to 120 characters
+fn snippet(element: &str) -> String {
+    let element = element.split_whitespace().collect::<Vec<_>>().join(" ");
+    if element.chars().count() > 120 {
+        format!("{}...", element.chars().take(117).collect::<String>())
+    } else {
+        element
+    }
+}
+
+/// A SARIF 2.1.0 log, the format GitHub Code Scanning uploads
+fn sarif(issues: &[Issue]) -> serde_json::Value {
+    let rules = RULES
+        .iter()
+        .map(|(id, wcag, description, help_uri)| {
+            json!({
+                "id": id,
+                "shortDescription": {"text": description},
+                "fullDescription": {"text": format!("{description} (WCAG {wcag})")},
+                "helpUri": help_uri,
+            })
+        })
+        .collect::<Vec<_>>();
+    let results = issues
+        .iter()
+        .map(|issue| {
+            let uri = issue.file.to_string_lossy().replace('\\', "/");
+            let message = if issue.element.is_empty() {
+                issue.message.clone()
+            } else {
+                format!("{}: {}", issue.message, issue.element)
+            };
+            json!({
+                "ruleId": issue.rule,
+                "level": "warning",
+                "message": {"text": message},
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {"uri": uri.trim_start_matches("./")},
+                        "region": {"startLine": issue.line},
+                    },
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "marmite",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(html: &str) -> Vec<&'static str> {
+        audit_html(Path::new("page.html"), html)
+            .iter()
+            .map(|issue| issue.rule)
+            .collect()
+    }
+
+    const VALID: &str = r#"<html lang="en"><body><h1>Title</h1>
+<img src="a.png" alt="A figure"><a href="./b.html">Next</a>
+<label for="q">Search</label><input id="q"><h2>Part</h2></body></html>"#;
+
+    #[test]
+    fn valid_page_has_no_issues() {
+        assert!(rules(VALID).is_empty());
+    }
+
+    #[test]
+    fn every_rule_reports_its_issue() {
+        let cases = [
+            ("<html lang=\"en\">", "<html>", "html-lang"),
+            ("alt=\"A figure\"", "", "image-alt"),
+            ("<label for=\"q\">Search</label>", "", "input-label"),
+            (">Next</a>", "></a>", "link-name"),
+            ("<h1>Title</h1>", "<h2>Title</h2>", "page-has-one-h1"),
+            ("<h2>Part</h2>", "<h3>Part</h3>", "heading-order"),
+        ];
+        for (valid, broken, rule) in cases {
+            assert_eq!(rules(&VALID.replace(valid, broken)), [rule], "{rule}");
+        }
+    }
+
+    #[test]
+    fn inputs_are_labelled_by_aria_label_or_an_enclosing_label() {
+        let html = VALID.replace("<label for=\"q\">Search</label><input id=\"q\">", "");
+        for input in [
+            r#"<input aria-label="Search">"#,
+            "<label>Search <input></label>",
+            r#"<input type="hidden">"#,
+        ] {
+            assert!(rules(&html.replace("<h2>", &format!("{input}<h2>"))).is_empty());
+        }
+    }
+
+    #[test]
+    fn redirect_pages_are_skipped() {
+        let html = r#"<!DOCTYPE html>
+<html><head><meta http-equiv="refresh" content="0; url=a.html"></head>
+<body><a href="a.html">A</a></body></html>"#;
+        assert!(rules(html).is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::cli::Cli;
+use crate::site;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Builds the site `runs` times without the build cache, each to a fresh
+/// temporary folder, and prints the min, max and p50/p90/p99 build times,
+/// in total and per phase (`--benchmark`), as JSON with `--format json`.
+pub fn run(config_path: &Path, input_folder: &Path, cli_args: &Arc<Cli>) {
+    // the cache would make every run after the first skip parsing
+    let mut uncached = Cli::clone(cli_args);
+    uncached.no_cache = true;
+    let cli_args = &Arc::new(uncached);
+    let runs = cli_args.runs.max(1);
+    let mut totals = Vec::with_capacity(runs);
+    let mut phases = Vec::with_capacity(runs);
+    for run in 0..runs {
+        let output_folder = Arc::new(
+            std::env::temp_dir().join(format!("marmite-benchmark-{}-{run}", std::process::id())),
+        );
+        let started = Instant::now();
+        let timings = site::generate(
+            config_path,
+            input_folder,
+            &output_folder,
+            false,
+            false,
+            &cli_args.bind,
+            cli_args,
+        );
+        totals.push(started.elapsed());
+        phases.push(timings);
+        let _ = fs::remove_dir_all(output_folder.as_path());
+    }
+
+    let rows = [
+        ("total", totals),
+        ("content", phases.iter().map(|t| t.content).collect()),
+        ("render", phases.iter().map(|t| t.render).collect()),
+        ("io", phases.iter().map(|t| t.io).collect()),
+    ]
+    .map(|(phase, mut durations)| {
+        durations.sort();
+        (phase, Stats::new(&durations))
+    });
+
+    if cli_args.format == "json" {
+        let json = rows
+            .iter()
+            .map(|(phase, stats)| (phase.to_string(), stats.to_json()))
+            .collect::<serde_json::Map<_, _>>();
+        let report = serde_json::json!({"runs": runs, "phases": json});
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).unwrap_or_default()
+        );
+        return;
+    }
+    println!("{runs} runs of {}", input_folder.display());
+    println!(
+        "{:<8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "phase", "min", "p50", "p90", "p99", "max"
+    );
+    for (phase, stats) in &rows {
+        println!(
+            "{:<8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            phase,
+            millis(stats.min),
+            millis(stats.p50),
+            millis(stats.p90),
+            millis(stats.p99),
+            millis(stats.max)
+        );
+    }
+}
+
+struct Stats {
+    min: Duration,
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl Stats {
+    /// `sorted` must not be empty
+    fn new(sorted: &[Duration]) -> Self {
+        Stats {
+            min: sorted[0],
+            p50: percentile(sorted, 50),
+            p90: percentile(sorted, 90),
+            p99: percentile(sorted, 99),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        serde_json::json!({
+            "min_ms": ms(self.min),
+            "p50_ms": ms(self.p50),
+            "p90_ms": ms(self.p90),
+            "p99_ms": ms(self.p99),
+            "max_ms": ms(self.max),
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted durations
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,139 @@
+use crate::config::Marmite;
+use crate::content::Content;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Folder in the input folder for the files kept between builds, out of
+/// the output folder so they are never published
+const STATE_DIR: &str = ".marmite";
+
+const CACHE_FILE: &str = "cache.json";
+
+/// The folder of the build cache and slug registry of `input_folder`
+pub fn state_dir(input_folder: &Path) -> PathBuf {
+    input_folder.join(STATE_DIR)
+}
+
+/// Parsed contents from the previous build, keyed by source path, so
+/// unchanged files skip parsing on the next build, every page is still
+/// rendered.
+#[derive(Serialize, Deserialize, Default)]
+pub struct BuildCache {
+    /// Hash of the config and templates the entries were built with
+    pub site_hash: String,
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    mtime: u64,
+    hash: String,
+    content: Content,
+}
+
+impl BuildCache {
+    pub fn new(site_hash: String) -> Self {
+        BuildCache {
+            site_hash,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Loads the cache from `state_dir`, discarding it when it was written
+    /// for a different config or set of templates.
+    pub fn load(state_dir: &Path, site_hash: &str) -> Self {
+        let cache_path = state_dir.join(CACHE_FILE);
+        let cache = fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<BuildCache>(&raw).ok());
+        match cache {
+            Some(cache) if cache.site_hash == site_hash => {
+                debug!("Loaded {} cached entries", cache.entries.len());
+                cache
+            }
+            Some(_) => {
+                info!("Config or templates changed, rebuilding everything");
+                BuildCache::new(site_hash.to_string())
+            }
+            None => BuildCache::new(site_hash.to_string()),
+        }
+    }
+
+    /// Returns the cached content for `path` if the file did not change
+    pub fn get(&self, path: &Path) -> Option<Content> {
+        let entry = self.entries.get(path)?;
+        if get_mtime(path)? != entry.mtime && hash_file(path)? != entry.hash {
+            return None;
+        }
+        let mut content = entry.content.clone();
+        content.source_path = path.to_path_buf();
+        Some(content)
+    }
+
+    pub fn insert(&mut self, path: &Path, content: &Content) {
+        if let (Some(mtime), Some(hash)) = (get_mtime(path), hash_file(path)) {
+            self.entries.insert(
+                path.to_path_buf(),
+                CacheEntry {
+                    mtime,
+                    hash,
+                    content: content.clone(),
+                },
+            );
+        }
+    }
+
+    pub fn save(&self, state_dir: &Path) {
+        let cache_path = state_dir.join(CACHE_FILE);
+        let result = serde_json::to_string(self)
+            .map_err(|e| e.to_string())
+            .and_then(|raw| {
+                fs::create_dir_all(state_dir)
+                    .and_then(|()| fs::write(&cache_path, raw))
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            debug!("Unable to write {}: {}", cache_path.display(), e);
+        }
+    }
+}
+
+/// Hashes everything that affects every page: the config with its
+/// `MARMITE_*` overrides, the custom templates and the marmite version
+/// (which ships the embedded templates).
+pub fn site_hash(site: &Marmite, templates_dir: &Path) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(serde_json::to_string(site).unwrap_or_default());
+    let mut templates = WalkDir::new(templates_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().is_file())
+        .map(walkdir::DirEntry::into_path)
+        .collect::<Vec<_>>();
+    templates.sort();
+    for template in templates {
+        hasher.update(template.to_string_lossy().as_bytes());
+        if let Ok(bytes) = fs::read(&template) {
+            hasher.update(bytes);
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    Some(format!("{:x}", Sha256::digest(bytes)))
+}
+
+fn get_mtime(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let nanos = modified.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+    u64::try_from(nanos).ok()
+}
@@ -1,16 +1,38 @@
 #![allow(clippy::struct_excessive_bools)]
-use clap::Parser;
+use clap::{ArgGroup, Parser};
 use std::path::PathBuf;
 
+/// Config file read from the input folder when `--config` is not given
+pub const DEFAULT_CONFIG: &str = "marmite.yaml";
+
 /// Command Line Argument Parser for Marmite CLI
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
+#[command(group(ArgGroup::new("fixable").args(["lint_markdown", "check_encoding"])))]
 pub struct Cli {
     /// Input folder containing markdown files
-    pub input_folder: PathBuf,
+    #[arg(required_unless_present_any = ["init", "convert", "version_check", "deploy_s3"])]
+    pub input_folder: Option<PathBuf>,
 
     /// Output folder to generate the site
-    pub output_folder: PathBuf,
+    #[arg(required_unless_present_any = [
+        "init",
+        "new_post",
+        "new_page",
+        "validate_config",
+        "init_github_pages",
+        "convert",
+        "version_check",
+        "benchmark",
+        "export_epub",
+        "export_pdf",
+        "stats",
+        "deploy_s3",
+        "lint_markdown",
+        "translate",
+        "check_encoding"
+    ])]
+    pub output_folder: Option<PathBuf>,
 
     /// Serve the site with a built-in HTTP server
     #[arg(long)]
@@ -20,18 +42,165 @@ pub struct Cli {
     #[arg(long)]
     pub watch: bool,
 
+    /// Show a desktop notification after each --watch rebuild
+    #[arg(long, requires = "watch")]
+    pub notify_send: bool,
+
     /// Address to bind the server (defaults to localhost:8000)
     #[arg(long, default_value = "localhost:8000")]
     pub bind: String,
 
     /// Path to custom configuration file (defaults to marmite.yaml)
-    #[arg(long, default_value = "marmite.yaml")]
+    #[arg(long, default_value = DEFAULT_CONFIG)]
     pub config: String,
 
+    /// Include content marked as `draft: true` (implied by --serve)
+    #[arg(long)]
+    pub include_drafts: bool,
+
+    /// Include posts dated in the future
+    #[arg(long)]
+    pub future: bool,
+
+    /// Print counts, tags, posts per month and post lengths without writing
+    /// the site
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Output format of --stats and --benchmark (table or json) and --audit
+    /// (table, json or sarif)
+    #[arg(long, default_value = "table", value_parser = ["table", "json", "sarif"])]
+    pub format: String,
+
+    /// Delete the output folder before building
+    #[arg(long)]
+    pub clean: bool,
+
+    /// Do not ask for confirmation (e.g. when using --clean)
+    #[arg(long)]
+    pub yes: bool,
+
+    /// Validate content and templates without writing the site
+    #[arg(long)]
+    pub check: bool,
+
+    /// Warn instead of failing when two contents share the same slug
+    #[arg(long)]
+    pub allow_slug_collision: bool,
+
+    /// Ignore the build cache in `.marmite/` of the input folder and parse
+    /// every file again
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Warn about unknown keys in the config file and exit
+    #[arg(long)]
+    pub validate_config: bool,
+
+    /// Build the site --runs times to temporary folders and print the build times
+    #[arg(long)]
+    pub benchmark: bool,
+
+    /// Number of builds for --benchmark
+    #[arg(long, default_value_t = 10)]
+    pub runs: usize,
+
+    /// Build the site and print `redirect_from` entries for the old slugs of
+    /// the contents, recorded by every build in `.marmite/slugs.json` of the
+    /// input folder
+    #[arg(long)]
+    pub migrate_slugs: bool,
+
+    /// Add the --migrate-slugs entries to the frontmatter of the files
+    #[arg(long)]
+    pub write: bool,
+
+    /// Build the site and check the generated HTML for accessibility issues
+    #[arg(long)]
+    pub audit: bool,
+
+    /// Exit successfully even when --audit finds issues
+    #[arg(long)]
+    pub exit_zero: bool,
+
+    /// Check the markdown files of the content folder with the markdownlint
+    /// rules MD001, MD009, MD010, MD012, MD022 and MD041
+    #[arg(long)]
+    pub lint_markdown: bool,
+
+    /// Check that the content files are valid UTF-8 without a byte order mark
+    #[arg(long)]
+    pub check_encoding: bool,
+
+    /// Remove the trailing spaces and the blank lines ending the files found
+    /// by --lint-markdown, or the byte order marks found by --check-encoding
+    #[arg(long, requires = "fixable")]
+    pub fix: bool,
+
+    /// Check crates.io for a newer version of marmite
+    #[arg(long)]
+    pub version_check: bool,
+
     /// Print debug messages
     #[arg(long)]
     pub debug: bool,
 
+    /// Create a new project in FOLDER with a sample post, templates and a
+    /// .gitignore for `.marmite/`
+    #[arg(long, value_name = "FOLDER")]
+    pub init: Option<PathBuf>,
+
+    /// Render a single markdown FILE to an OUTPUT html file with the site
+    /// config and templates
+    #[arg(long, num_args = 2, value_names = ["FILE", "OUTPUT"])]
+    pub convert: Option<Vec<PathBuf>>,
+
+    /// Package the dated posts as an EPUB3 ebook written to OUTPUT
+    #[arg(long, value_name = "OUTPUT")]
+    pub export_epub: Option<PathBuf>,
+
+    /// Build the site to a temporary folder and render its index to an OUTPUT
+    /// PDF file with WeasyPrint
+    #[arg(long, value_name = "OUTPUT")]
+    pub export_pdf: Option<PathBuf>,
+
+    /// Page size of --export-pdf
+    #[arg(long, default_value = "A4", value_parser = ["A4", "Letter"])]
+    pub pdf_page_size: String,
+
+    /// Upload the changed files of a built site DIR to an S3 or R2 BUCKET and
+    /// delete the objects of removed files. Reads AWS_ACCESS_KEY_ID,
+    /// AWS_SECRET_ACCESS_KEY, AWS_REGION and, for R2 and other S3 compatible
+    /// storages, AWS_ENDPOINT_URL
+    #[arg(long, num_args = 2, value_names = ["DIR", "BUCKET"])]
+    pub deploy_s3: Option<Vec<String>>,
+
+    /// Print the uploads and deletions of --deploy-s3 without making them
+    #[arg(long, requires = "deploy_s3")]
+    pub dry_run: bool,
+
+    /// Create a draft post in the content folder from its title
+    #[arg(long, value_name = "TITLE")]
+    pub new_post: Option<String>,
+
+    /// Create a page shown in the menu in the content folder from its title
+    #[arg(long, value_name = "TITLE")]
+    pub new_page: Option<String>,
+
+    /// Copy the post or page SLUG to a draft translation to LANG, a two-letter
+    /// code, with a TRANSLATE comment above each block of text
+    #[arg(long, num_args = 2, value_names = ["SLUG", "LANG"])]
+    pub translate: Option<Vec<String>>,
+
+    /// Pre-translate the --translate draft with this DeepL API key, the
+    /// frontmatter and code blocks are kept untranslated
+    #[arg(long, value_name = "KEY", requires = "translate")]
+    pub deepl_key: Option<String>,
+
+    /// Write a GitHub Actions workflow deploying the site to GitHub Pages
+    #[arg(long)]
+    pub init_github_pages: bool,
+
     /// Initialize templates in the project
     #[arg(long)]
     pub init_templates: bool,
@@ -1,8 +1,56 @@
+use crate::cli::{Cli, DEFAULT_CONFIG};
+use log::{error, info, warn};
+use schemars::schema::Schema;
+use schemars::{schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
 use std::collections::HashMap;
+use std::path::Path;
+use std::{fs, process};
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// Parses the config as TOML or JSON based on the extension of `path`,
+/// YAML otherwise. An empty config means all the defaults.
+pub fn parse_config(config_content: &str, path: &Path) -> Result<Marmite, String> {
+    if config_content.trim().is_empty() {
+        return Ok(Marmite::default());
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => {
+            toml::from_str(config_content).map_err(|e| format!("Failed to parse config TOML: {e}"))
+        }
+        Some("json") => serde_json::from_str(config_content)
+            .map_err(|e| format!("Failed to parse config JSON: {e}")),
+        _ => serde_yaml::from_str(config_content)
+            .map_err(|e| format!("Failed to parse config YAML: {e}")),
+    }
+}
+
+/// Reads the config file. Only the default `marmite.yaml` is optional, when
+/// missing the defaults are used, a `--config` file that can't be read is an
+/// error.
+pub fn read_config(config_path: &Path, cli_args: &Cli) -> Result<String, String> {
+    match fs::read_to_string(config_path) {
+        Ok(config_content) => {
+            info!("Config loaded from: {}", config_path.display());
+            Ok(config_content)
+        }
+        Err(e) if cli_args.config == DEFAULT_CONFIG => {
+            info!(
+                "No config found at '{}', using the defaults: {}",
+                config_path.display(),
+                e
+            );
+            Ok(String::new())
+        }
+        Err(e) => Err(format!(
+            "Unable to read config file '{}': {}",
+            config_path.display(),
+            e
+        )),
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, JsonSchema)]
 pub struct Marmite {
     #[serde(default = "default_name")]
     pub name: String,
@@ -10,11 +58,21 @@ pub struct Marmite {
     pub tagline: String,
     #[serde(default = "default_url")]
     pub url: String,
+    #[serde(default = "default_author")]
+    pub default_author: String,
     #[serde(default = "default_footer")]
     pub footer: String,
     #[serde(default = "default_pagination")]
     pub pagination: usize,
 
+    #[serde(default = "default_reading_speed_wpm")]
+    pub reading_speed_wpm: u32,
+
+    #[serde(default = "default_excerpt_length")]
+    pub excerpt_length: usize,
+    #[serde(default = "default_excerpt_more_text")]
+    pub excerpt_more_text: String,
+
     #[serde(default = "default_list_title")]
     pub list_title: String,
     #[serde(default = "default_pages_title")]
@@ -23,10 +81,27 @@ pub struct Marmite {
     pub tags_title: String,
     #[serde(default = "default_tags_content_title")]
     pub tags_content_title: String,
+    /// Canonical tags and the synonyms replaced by them, case-insensitive
+    #[serde(default = "default_tag_aliases")]
+    pub tag_aliases: HashMap<String, Vec<String>>,
+    #[serde(default = "default_categories_title")]
+    pub categories_title: String,
+    #[serde(default = "default_categories_content_title")]
+    pub categories_content_title: String,
+    #[serde(default = "default_series_content_title")]
+    pub series_content_title: String,
     #[serde(default = "default_archives_title")]
     pub archives_title: String,
     #[serde(default = "default_archives_content_title")]
     pub archives_content_title: String,
+    #[serde(default = "default_archive")]
+    pub archive: bool,
+
+    #[serde(default = "default_permalink")]
+    pub permalink: String,
+
+    #[serde(default = "default_lang")]
+    pub default_lang: String,
 
     #[serde(default = "default_content_path")]
     pub content_path: String,
@@ -43,15 +118,327 @@ pub struct Marmite {
     pub card_image: String,
     #[serde(default = "default_logo_image")]
     pub logo_image: String,
+    #[serde(default = "default_twitter_handle")]
+    pub twitter_handle: Option<String>,
+    #[serde(default = "default_epub_cover")]
+    pub epub_cover: Option<String>,
 
     #[serde(default = "default_enable_search")]
     pub enable_search: bool,
 
+    #[serde(default = "default_asset_fingerprint")]
+    pub asset_fingerprint: bool,
+    #[serde(default = "default_scss")]
+    pub scss: bool,
+    #[serde(default = "default_minify_html")]
+    pub minify_html: bool,
+
+    #[serde(default = "default_wiki_links")]
+    pub wiki_links: bool,
+    #[serde(default = "default_notebooks")]
+    pub notebooks: bool,
+
+    #[serde(default = "default_syntax_highlight")]
+    pub syntax_highlight: bool,
+    #[serde(default = "default_syntax_theme")]
+    pub syntax_theme: String,
+
+    #[serde(default = "default_math")]
+    pub math: bool,
+    #[serde(default = "default_mermaid")]
+    pub mermaid: bool,
+
+    #[serde(default = "default_atom_feed")]
+    pub atom_feed: bool,
+    #[serde(default = "default_atom_path")]
+    pub atom_path: String,
+
+    #[serde(default = "default_json_feed")]
+    pub json_feed: bool,
+
+    #[serde(default = "default_sitemap")]
+    pub sitemap: bool,
+    #[serde(default = "default_sitemap_changefreq")]
+    pub sitemap_changefreq: String,
+
+    #[serde(default = "default_robots_txt")]
+    pub robots_txt: bool,
+    #[serde(default = "default_robots_txt_extra")]
+    pub robots_txt_extra: Option<String>,
+
+    #[serde(default = "default_netlify_headers")]
+    pub netlify_headers: bool,
+    #[serde(default = "default_netlify_headers_extra")]
+    pub netlify_headers_extra: Vec<String>,
+
     #[serde(default = "default_menu")]
     pub menu: Option<Vec<(String, String)>>,
 
     #[serde(default = "default_extra")]
+    #[schemars(with = "Option<HashMap<String, serde_json::Value>>")]
     pub extra: Option<HashMap<String, Value>>,
+
+    // Computed from `url`, not configurable
+    #[serde(skip_deserializing)]
+    pub feed_url: String,
+    #[serde(skip_deserializing)]
+    pub sitemap_url: String,
+}
+
+impl Default for Marmite {
+    /// The config used when there is no config file, same as an empty one
+    fn default() -> Self {
+        Marmite {
+            name: default_name(),
+            tagline: default_tagline(),
+            url: default_url(),
+            default_author: default_author(),
+            footer: default_footer(),
+            pagination: default_pagination(),
+            reading_speed_wpm: default_reading_speed_wpm(),
+            excerpt_length: default_excerpt_length(),
+            excerpt_more_text: default_excerpt_more_text(),
+            list_title: default_list_title(),
+            pages_title: default_pages_title(),
+            tags_title: default_tags_title(),
+            tags_content_title: default_tags_content_title(),
+            tag_aliases: default_tag_aliases(),
+            categories_title: default_categories_title(),
+            categories_content_title: default_categories_content_title(),
+            series_content_title: default_series_content_title(),
+            archives_title: default_archives_title(),
+            archives_content_title: default_archives_content_title(),
+            archive: default_archive(),
+            permalink: default_permalink(),
+            default_lang: default_lang(),
+            content_path: default_content_path(),
+            site_path: default_site_path(),
+            templates_path: default_templates_path(),
+            static_path: default_static_path(),
+            media_path: default_media_path(),
+            card_image: default_card_image(),
+            logo_image: default_logo_image(),
+            twitter_handle: default_twitter_handle(),
+            epub_cover: default_epub_cover(),
+            enable_search: default_enable_search(),
+            asset_fingerprint: default_asset_fingerprint(),
+            scss: default_scss(),
+            minify_html: default_minify_html(),
+            wiki_links: default_wiki_links(),
+            notebooks: default_notebooks(),
+            syntax_highlight: default_syntax_highlight(),
+            syntax_theme: default_syntax_theme(),
+            math: default_math(),
+            mermaid: default_mermaid(),
+            atom_feed: default_atom_feed(),
+            atom_path: default_atom_path(),
+            json_feed: default_json_feed(),
+            sitemap: default_sitemap(),
+            sitemap_changefreq: default_sitemap_changefreq(),
+            robots_txt: default_robots_txt(),
+            robots_txt_extra: default_robots_txt_extra(),
+            netlify_headers: default_netlify_headers(),
+            netlify_headers_extra: default_netlify_headers_extra(),
+            menu: default_menu(),
+            extra: default_extra(),
+            feed_url: String::new(),
+            sitemap_url: String::new(),
+        }
+    }
+}
+
+impl Marmite {
+    /// Overrides fields from `MARMITE_<FIELD>` environment variables, e.g.
+    /// `MARMITE_URL`. Only text and number fields, other names are ignored.
+    pub fn apply_env_overrides(&mut self) {
+        for (key, value) in std::env::vars() {
+            if let Some(field) = key.strip_prefix("MARMITE_") {
+                self.set_field(&field.to_lowercase(), value);
+            }
+        }
+    }
+
+    /// Resets the values the build can't use to their default, once the
+    /// config file and the environment overrides are applied.
+    pub fn check_values(&mut self) {
+        if self.pagination == 0 {
+            warn!(
+                "Ignoring pagination: 0, lists need at least one item per page, using {}",
+                default_pagination()
+            );
+            self.pagination = default_pagination();
+        }
+        // without syntect the templates keep highlighting with highlight.js
+        #[cfg(not(feature = "syntax-highlight"))]
+        if self.syntax_highlight {
+            warn!(
+                "Ignoring syntax_highlight: marmite was built without the \
+                `syntax-highlight` feature, code is highlighted by highlight.js"
+            );
+            self.syntax_highlight = false;
+        }
+    }
+
+    fn set_field(&mut self, field: &str, value: String) {
+        match field {
+            "pagination" => match value.parse() {
+                Ok(number) => self.pagination = number,
+                Err(e) => warn!("Ignoring MARMITE_PAGINATION={}: {}", value, e),
+            },
+            "reading_speed_wpm" => match value.parse() {
+                Ok(number) => self.reading_speed_wpm = number,
+                Err(e) => warn!("Ignoring MARMITE_READING_SPEED_WPM={}: {}", value, e),
+            },
+            "excerpt_length" => match value.parse() {
+                Ok(number) => self.excerpt_length = number,
+                Err(e) => warn!("Ignoring MARMITE_EXCERPT_LENGTH={}: {}", value, e),
+            },
+            "twitter_handle" => self.twitter_handle = Some(value),
+            "epub_cover" => self.epub_cover = Some(value),
+            _ => {
+                let target = match field {
+                    "name" => &mut self.name,
+                    "tagline" => &mut self.tagline,
+                    "url" => &mut self.url,
+                    "default_author" => &mut self.default_author,
+                    "footer" => &mut self.footer,
+                    "excerpt_more_text" => &mut self.excerpt_more_text,
+                    "list_title" => &mut self.list_title,
+                    "pages_title" => &mut self.pages_title,
+                    "tags_title" => &mut self.tags_title,
+                    "tags_content_title" => &mut self.tags_content_title,
+                    "categories_title" => &mut self.categories_title,
+                    "categories_content_title" => &mut self.categories_content_title,
+                    "series_content_title" => &mut self.series_content_title,
+                    "archives_title" => &mut self.archives_title,
+                    "archives_content_title" => &mut self.archives_content_title,
+                    "permalink" => &mut self.permalink,
+                    "default_lang" => &mut self.default_lang,
+                    "content_path" => &mut self.content_path,
+                    "site_path" => &mut self.site_path,
+                    "templates_path" => &mut self.templates_path,
+                    "static_path" => &mut self.static_path,
+                    "media_path" => &mut self.media_path,
+                    "card_image" => &mut self.card_image,
+                    "logo_image" => &mut self.logo_image,
+                    "syntax_theme" => &mut self.syntax_theme,
+                    "atom_path" => &mut self.atom_path,
+                    "sitemap_changefreq" => &mut self.sitemap_changefreq,
+                    _ => return,
+                };
+                *target = value;
+            }
+        }
+    }
+}
+
+/// Checks the config file for keys `Marmite` does not know about (keys
+/// under `extra` are free), for `--validate-config`. Unknown keys are only
+/// warnings, a config that can't be parsed exits with 1.
+pub fn validate_config(config_path: &Path) {
+    let config_content = fs::read_to_string(config_path).unwrap_or_else(|e| {
+        error!("Unable to read '{}': {}", config_path.display(), e);
+        process::exit(1);
+    });
+    let raw = match config_path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => {
+            toml::from_str::<serde_json::Value>(&config_content).map_err(|e| e.to_string())
+        }
+        Some("json") => {
+            serde_json::from_str::<serde_json::Value>(&config_content).map_err(|e| e.to_string())
+        }
+        _ => serde_yaml::from_str::<serde_json::Value>(&config_content).map_err(|e| e.to_string()),
+    };
+    let raw = match raw {
+        Ok(serde_json::Value::Object(raw)) => raw,
+        Ok(serde_json::Value::Null) => serde_json::Map::new(),
+        Ok(_) => {
+            error!("{} must be a mapping of options", config_path.display());
+            process::exit(1);
+        }
+        Err(e) => {
+            error!("Unable to parse {}: {}", config_path.display(), e);
+            process::exit(1);
+        }
+    };
+
+    // computed fields are in the schema as read only, setting them has no effect
+    let schema = schema_for!(Marmite);
+    let (computed_keys, known_keys): (Vec<_>, Vec<_>) = schema
+        .schema
+        .object
+        .map(|object| object.properties)
+        .unwrap_or_default()
+        .into_iter()
+        .partition(|(_, property)| match property {
+            Schema::Object(object) => object.metadata.as_ref().is_some_and(|m| m.read_only),
+            Schema::Bool(_) => false,
+        });
+    let known_keys = known_keys
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+
+    let mut warnings = 0;
+    for key in raw.keys() {
+        if known_keys.contains(key) {
+            continue;
+        }
+        warnings += 1;
+        if computed_keys.iter().any(|(computed, _)| computed == key) {
+            warn!(
+                "Config key `{}` is computed by marmite and will be ignored",
+                key
+            );
+            continue;
+        }
+        match closest_key(key, &known_keys) {
+            Some(suggestion) => warn!(
+                "Unknown config key `{}`, did you mean `{}`?",
+                key, suggestion
+            ),
+            None => warn!(
+                "Unknown config key `{}`, custom data goes under `extra`",
+                key
+            ),
+        }
+    }
+
+    // known keys can still have the wrong type (e.g. `pagination: ten`)
+    if let Err(e) = parse_config(&config_content, config_path) {
+        error!("{}", e);
+        process::exit(1);
+    }
+    info!(
+        "{} is valid with {} warning(s)",
+        config_path.display(),
+        warnings
+    );
+}
+
+/// The known key with the smallest edit distance, if it is close enough
+fn closest_key<'a>(key: &str, known_keys: &'a [String]) -> Option<&'a str> {
+    known_keys
+        .iter()
+        .map(|known| (edit_distance(key, known), known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known.as_str())
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
 }
 
 fn default_name() -> String {
@@ -66,6 +453,10 @@ fn default_url() -> String {
     String::new()
 }
 
+fn default_author() -> String {
+    String::new()
+}
+
 fn default_footer() -> String {
     r#"<div>Site generated with <a href="https://github.com/rochacbruno/marmite">Marmite</a> | <small><a href="https://creativecommons.org/licenses/by-nc-sa/4.0/">CC-BY_NC-SA</a></small></div>"#.to_string()
 }
@@ -74,6 +465,18 @@ fn default_pagination() -> usize {
     10usize
 }
 
+fn default_reading_speed_wpm() -> u32 {
+    200
+}
+
+fn default_excerpt_length() -> usize {
+    300
+}
+
+fn default_excerpt_more_text() -> String {
+    "Read more".to_string()
+}
+
 fn default_list_title() -> String {
     "Posts".to_string()
 }
@@ -86,6 +489,22 @@ fn default_tags_content_title() -> String {
     "Posts tagged with '$tag'".to_string()
 }
 
+fn default_tag_aliases() -> HashMap<String, Vec<String>> {
+    HashMap::new()
+}
+
+fn default_categories_title() -> String {
+    "Categories".to_string()
+}
+
+fn default_categories_content_title() -> String {
+    "Posts in '$category'".to_string()
+}
+
+fn default_series_content_title() -> String {
+    "Series '$series'".to_string()
+}
+
 fn default_pages_title() -> String {
     "Pages".to_string()
 }
@@ -98,10 +517,22 @@ fn default_archives_content_title() -> String {
     "Posts from '$year'".to_string()
 }
 
+fn default_archive() -> bool {
+    true
+}
+
+fn default_permalink() -> String {
+    "{slug}".to_string()
+}
+
 fn default_site_path() -> String {
     String::new()
 }
 
+fn default_lang() -> String {
+    "en".to_string()
+}
+
 fn default_content_path() -> String {
     "content".to_string()
 }
@@ -126,10 +557,90 @@ fn default_logo_image() -> String {
     String::new()
 }
 
+fn default_twitter_handle() -> Option<String> {
+    None
+}
+
+fn default_epub_cover() -> Option<String> {
+    None
+}
+
 fn default_enable_search() -> bool {
     false
 }
 
+fn default_asset_fingerprint() -> bool {
+    false
+}
+
+fn default_scss() -> bool {
+    false
+}
+
+fn default_wiki_links() -> bool {
+    false
+}
+
+fn default_notebooks() -> bool {
+    false
+}
+
+fn default_minify_html() -> bool {
+    false
+}
+
+fn default_syntax_highlight() -> bool {
+    false
+}
+
+fn default_syntax_theme() -> String {
+    "InspiredGitHub".to_string()
+}
+
+fn default_math() -> bool {
+    false
+}
+
+fn default_mermaid() -> bool {
+    false
+}
+
+fn default_atom_feed() -> bool {
+    true
+}
+
+fn default_atom_path() -> String {
+    "atom.xml".to_string()
+}
+
+fn default_json_feed() -> bool {
+    false
+}
+
+fn default_sitemap() -> bool {
+    true
+}
+
+fn default_sitemap_changefreq() -> String {
+    "weekly".to_string()
+}
+
+fn default_robots_txt() -> bool {
+    true
+}
+
+fn default_robots_txt_extra() -> Option<String> {
+    None
+}
+
+fn default_netlify_headers() -> bool {
+    false
+}
+
+fn default_netlify_headers_extra() -> Vec<String> {
+    Vec::new()
+}
+
 fn default_menu() -> Option<Vec<(String, String)>> {
     vec![
         ("Pages".to_string(), "pages.html".to_string()),
@@ -142,3 +653,117 @@ fn default_menu() -> Option<Vec<(String, String)>> {
 fn default_extra() -> Option<HashMap<String, Value>> {
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_parsed(config: &Marmite) {
+        assert_eq!(config.name, "My Blog");
+        assert_eq!(config.pagination, 5);
+        assert_eq!(
+            config.menu,
+            Some(vec![("About".to_string(), "about.html".to_string())])
+        );
+        let extra = config.extra.as_ref().unwrap();
+        assert_eq!(extra["colorscheme"], Value::from("nord"));
+    }
+
+    #[test]
+    fn parses_yaml() {
+        let content = "name: My Blog\npagination: 5\nmenu:\n  - [About, about.html]\nextra:\n  colorscheme: nord\n";
+        assert_parsed(&parse_config(content, Path::new("marmite.yaml")).unwrap());
+    }
+
+    #[test]
+    fn parses_toml() {
+        let content = "name = \"My Blog\"\npagination = 5\nmenu = [[\"About\", \"about.html\"]]\n\n[extra]\ncolorscheme = \"nord\"\n";
+        assert_parsed(&parse_config(content, Path::new("marmite.toml")).unwrap());
+    }
+
+    #[test]
+    fn parses_json() {
+        let content = r#"{"name": "My Blog", "pagination": 5, "menu": [["About", "about.html"]], "extra": {"colorscheme": "nord"}}"#;
+        assert_parsed(&parse_config(content, Path::new("marmite.json")).unwrap());
+    }
+
+    #[test]
+    fn other_extensions_are_yaml() {
+        let content = "name: My Blog\npagination: 5\nmenu:\n  - [About, about.html]\nextra:\n  colorscheme: nord\n";
+        assert_parsed(&parse_config(content, Path::new("marmite.yml")).unwrap());
+    }
+
+    #[test]
+    fn empty_config_is_the_default() {
+        let config = parse_config("\n", Path::new("marmite.toml")).unwrap();
+        assert_eq!(config.name, Marmite::default().name);
+    }
+
+    #[test]
+    fn errors_name_the_format() {
+        let toml = parse_config("name = ", Path::new("marmite.toml")).unwrap_err();
+        assert!(toml.starts_with("Failed to parse config TOML"));
+        let json = parse_config("{\"name\": ", Path::new("marmite.json")).unwrap_err();
+        assert!(json.starts_with("Failed to parse config JSON"));
+        let yaml = parse_config("name: [", Path::new("marmite.yaml")).unwrap_err();
+        assert!(yaml.starts_with("Failed to parse config YAML"));
+    }
+
+    #[test]
+    fn zero_pagination_is_the_default() {
+        let mut config = parse_config("pagination: 0", Path::new("marmite.yaml")).unwrap();
+        config.check_values();
+        assert_eq!(config.pagination, default_pagination());
+    }
+
+    #[cfg(not(feature = "syntax-highlight"))]
+    #[test]
+    fn syntax_highlight_needs_the_feature() {
+        let mut config = parse_config("syntax_highlight: true", Path::new("marmite.yaml")).unwrap();
+        config.check_values();
+        assert!(!config.syntax_highlight);
+    }
+
+    #[test]
+    fn overrides_set_the_field() {
+        let mut config = Marmite::default();
+        config.set_field("name", "From env".to_string());
+        config.set_field("pagination", "3".to_string());
+        config.set_field("excerpt_length", "80".to_string());
+        config.set_field("twitter_handle", "@marmite".to_string());
+        assert_eq!(config.name, "From env");
+        assert_eq!(config.pagination, 3);
+        assert_eq!(config.excerpt_length, 80);
+        assert_eq!(config.twitter_handle.as_deref(), Some("@marmite"));
+    }
+
+    #[test]
+    fn overrides_of_the_wrong_type_are_ignored() {
+        let mut config = Marmite::default();
+        config.set_field("pagination", "ten".to_string());
+        config.set_field("reading_speed_wpm", "-1".to_string());
+        config.set_field("excerpt_length", "".to_string());
+        assert_eq!(config.pagination, default_pagination());
+        assert_eq!(
+            config.reading_speed_wpm,
+            Marmite::default().reading_speed_wpm
+        );
+        assert_eq!(config.excerpt_length, Marmite::default().excerpt_length);
+    }
+
+    #[test]
+    fn unknown_or_structured_fields_are_not_overridden() {
+        let mut config = Marmite::default();
+        config.set_field("nonexistent", "value".to_string());
+        config.set_field("menu", "value".to_string());
+        assert_eq!(config.menu, Marmite::default().menu);
+    }
+
+    #[test]
+    fn zero_pagination_override_is_the_default() {
+        let mut config = Marmite::default();
+        config.set_field("pagination", "0".to_string());
+        config.check_values();
+        assert_eq!(config.pagination, default_pagination());
+    }
+}
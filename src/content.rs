@@ -1,25 +1,46 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Datelike, Month, NaiveDate, NaiveDateTime};
 use frontmatter_gen::{Frontmatter, Value};
-use log::error;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::collections::HashSet;
-use std::path::Path;
-use std::process;
+use std::path::{Path, PathBuf};
 use unicode_normalization::UnicodeNormalization;
 
-#[derive(Debug, Deserialize, Clone, Serialize)]
+#[derive(Debug, Deserialize, Clone, Serialize, Default)]
 pub struct Content {
     pub title: String,
     pub description: Option<String>,
+    pub excerpt: String,
     pub slug: String,
     pub html: String,
     pub tags: Vec<String>,
     pub date: Option<NaiveDateTime>,
-    pub extra: Option<Value>,
+    pub author: Option<String>,
+    pub draft: bool,
+    pub category: Option<String>,
+    pub series: Option<String>,
+    pub series_index: Option<u32>,
+    pub show_in_menu: bool,
+    pub menu_order: Option<i32>,
+    pub template: Option<String>,
+    pub toc: Vec<TocEntry>,
+    pub redirect_from: Vec<String>,
+    pub lang: Option<String>,
+    pub reading_time_minutes: u32,
+    pub word_count: usize,
+    pub char_count: usize,
+    pub extra: HashMap<String, serde_json::Value>,
     pub links_to: Option<Vec<String>>,
     pub back_links: Vec<Self>,
+    #[serde(skip)]
+    pub source_path: PathBuf,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct TocEntry {
+    pub level: u8,
+    pub text: String,
+    pub id: String,
 }
 
 pub fn get_title<'a>(frontmatter: &'a Frontmatter, html: &'a str) -> String {
@@ -42,6 +63,96 @@ pub fn get_description(frontmatter: &Frontmatter) -> Option<String> {
     None
 }
 
+pub fn get_author(frontmatter: &Frontmatter) -> Option<String> {
+    match frontmatter.get("author") {
+        Some(Value::String(author)) => Some(author.to_string()),
+        _ => None,
+    }
+}
+
+pub fn get_draft(frontmatter: &Frontmatter) -> bool {
+    frontmatter
+        .get("draft")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+pub fn get_category(frontmatter: &Frontmatter) -> Option<String> {
+    match frontmatter.get("category") {
+        Some(Value::String(category)) if !category.trim().is_empty() => {
+            Some(category.trim().to_string())
+        }
+        _ => None,
+    }
+}
+
+pub fn get_series(frontmatter: &Frontmatter) -> Option<String> {
+    match frontmatter.get("series") {
+        Some(Value::String(series)) if !series.trim().is_empty() => Some(series.trim().to_string()),
+        _ => None,
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn get_series_index(frontmatter: &Frontmatter) -> Option<u32> {
+    frontmatter
+        .get("series_index")
+        .and_then(Value::as_f64)
+        .filter(|index| *index >= 0.0)
+        .map(|index| index as u32)
+}
+
+pub fn get_show_in_menu(frontmatter: &Frontmatter) -> bool {
+    frontmatter
+        .get("show_in_menu")
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+pub fn get_menu_order(frontmatter: &Frontmatter) -> Option<i32> {
+    frontmatter
+        .get("menu_order")
+        .and_then(Value::as_f64)
+        .map(|order| order as i32)
+}
+
+/// Template rendering the content instead of `content.html`
+pub fn get_template(frontmatter: &Frontmatter) -> Option<String> {
+    frontmatter
+        .get("template")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|template| !template.is_empty())
+        .map(str::to_string)
+}
+
+/// Old paths of the content, as a YAML list or a comma separated string
+pub fn get_redirect_from(frontmatter: &Frontmatter) -> Vec<String> {
+    match frontmatter.get("redirect_from") {
+        Some(Value::Array(paths)) => paths
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(String::from)
+            .collect(),
+        Some(Value::String(paths)) => paths
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn get_toc_enabled(frontmatter: &Frontmatter) -> bool {
+    frontmatter
+        .get("toc")
+        .and_then(Value::as_bool)
+        .unwrap_or(true)
+}
+
 pub fn get_slug<'a>(frontmatter: &'a Frontmatter, path: &'a Path) -> String {
     if let Some(slug) = frontmatter.get("slug") {
         return slugify(&slug.to_string());
@@ -50,7 +161,8 @@ pub fn get_slug<'a>(frontmatter: &'a Frontmatter, path: &'a Path) -> String {
         return slugify(&title.to_string());
     }
 
-    let slug = path.file_stem().and_then(|stem| stem.to_str()).unwrap();
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap();
+    let (slug, _) = split_lang(stem);
     if let Some(date) = extract_date_from_filename(path) {
         return slug.replace(&format!("{}-", date.date()), "").to_string();
     }
@@ -58,7 +170,58 @@ pub fn get_slug<'a>(frontmatter: &'a Frontmatter, path: &'a Path) -> String {
     slug.to_string()
 }
 
-pub fn get_tags(frontmatter: &Frontmatter) -> Vec<String> {
+/// The `lang` of the frontmatter, else the language of `about.fr.md` style
+/// file names
+pub fn get_lang(frontmatter: &Frontmatter, path: &Path) -> Option<String> {
+    let lang = frontmatter
+        .get("lang")
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|lang| !lang.is_empty());
+    if let Some(lang) = lang {
+        return Some(lang.to_string());
+    }
+    let stem = path.file_stem().and_then(|stem| stem.to_str())?;
+    split_lang(stem).1.map(str::to_string)
+}
+
+/// Splits a two-letter ISO 639-1 suffix off a file stem, `about.fr` gives
+/// `("about", Some("fr"))`.
+fn split_lang(stem: &str) -> (&str, Option<&str>) {
+    match stem.rsplit_once('.') {
+        Some((name, lang)) if lang.len() == 2 && lang.bytes().all(|b| b.is_ascii_lowercase()) => {
+            (name, Some(lang))
+        }
+        _ => (stem, None),
+    }
+}
+
+/// Expands `{year}`, `{month}`, `{day}`, `{slug}` and `{title}` in the
+/// `permalink` pattern. Pages have no date and always use `{slug}`. A `/`
+/// in the pattern writes the content in subfolders of the output folder,
+/// so `/{year}/{month}/{slug}/` is written as `2024/06/my-post.html`.
+pub fn expand_permalink(permalink: &str, content: &Content) -> String {
+    let Some(date) = content.date else {
+        return content.slug.clone();
+    };
+    permalink
+        .replace("{year}", &date.format("%Y").to_string())
+        .replace("{month}", &date.format("%m").to_string())
+        .replace("{day}", &date.format("%d").to_string())
+        .replace("{title}", &slugify(&content.title))
+        .replace("{slug}", &content.slug)
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Tags of the frontmatter, the synonyms in `tag_aliases` replaced by their
+/// canonical tag and duplicates removed.
+pub fn get_tags(
+    frontmatter: &Frontmatter,
+    tag_aliases: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
     let tags: Vec<String> = match frontmatter.get("tags") {
         Some(Value::Array(tags)) => tags
             .iter()
@@ -68,10 +231,190 @@ pub fn get_tags(frontmatter: &Frontmatter) -> Vec<String> {
         Some(Value::String(tags)) => tags.split(',').map(str::trim).map(String::from).collect(),
         _ => Vec::new(),
     };
-    tags
+    let canonical = tag_aliases
+        .iter()
+        .flat_map(|(tag, synonyms)| {
+            std::iter::once(tag)
+                .chain(synonyms)
+                .map(move |alias| (alias.to_lowercase(), tag))
+        })
+        .collect::<HashMap<_, _>>();
+    let mut unique = Vec::new();
+    for tag in tags {
+        let tag = canonical
+            .get(&tag.to_lowercase())
+            .map_or(tag, |canonical| (*canonical).clone());
+        if !unique.contains(&tag) {
+            unique.push(tag);
+        }
+    }
+    unique
 }
 
-pub fn group_by_tags(posts: Vec<Content>) -> Vec<(String, Vec<Content>)> {
+pub fn group_by_category(posts: &[Content]) -> HashMap<String, Vec<Content>> {
+    let mut category_map: HashMap<String, Vec<Content>> = HashMap::new();
+    for post in posts {
+        if let Some(category) = &post.category {
+            category_map
+                .entry(category.clone())
+                .or_default()
+                .push(post.clone());
+        }
+    }
+    category_map
+}
+
+/// Groups posts by series, ordered by `series_index` then by date (oldest first)
+pub fn group_by_series(posts: &[Content]) -> HashMap<String, Vec<Content>> {
+    let mut series_map: HashMap<String, Vec<Content>> = HashMap::new();
+    for post in posts {
+        if let Some(series) = &post.series {
+            series_map
+                .entry(series.clone())
+                .or_default()
+                .push(post.clone());
+        }
+    }
+    for series_posts in series_map.values_mut() {
+        series_posts.sort_by_key(|p| (p.series_index.unwrap_or(u32::MAX), p.date));
+    }
+    series_map
+}
+
+/// Pages with `show_in_menu: true`, ordered by `menu_order` then by title,
+/// the pages without `menu_order` last
+pub fn menu_pages(pages: &[Content]) -> Vec<&Content> {
+    let mut menu_pages = pages
+        .iter()
+        .filter(|page| page.show_in_menu)
+        .collect::<Vec<_>>();
+    menu_pages.sort_by(|a, b| {
+        let order = |page: &Content| page.menu_order.unwrap_or(i32::MAX);
+        order(a).cmp(&order(b)).then_with(|| a.title.cmp(&b.title))
+    });
+    menu_pages
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveYear {
+    pub year: i32,
+    pub count: usize,
+    pub months: Vec<ArchiveMonth>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveMonth {
+    pub name: String,
+    pub number: u32,
+    pub count: usize,
+}
+
+/// Groups dated posts by year and month, both newest first
+pub fn group_by_archive(posts: &[Content]) -> Vec<(ArchiveYear, Vec<Vec<Content>>)> {
+    let mut year_map: HashMap<i32, HashMap<u32, Vec<Content>>> = HashMap::new();
+    for post in posts {
+        if let Some(date) = post.date {
+            year_map
+                .entry(date.year())
+                .or_default()
+                .entry(date.month())
+                .or_default()
+                .push(post.clone());
+        }
+    }
+
+    let mut archive = year_map
+        .into_iter()
+        .map(|(year, month_map)| {
+            let mut months = month_map.into_iter().collect::<Vec<_>>();
+            months.sort_by_key(|(number, _)| std::cmp::Reverse(*number));
+            let archive_year = ArchiveYear {
+                year,
+                count: months.iter().map(|(_, posts)| posts.len()).sum(),
+                months: months
+                    .iter()
+                    .map(|(number, posts)| ArchiveMonth {
+                        name: month_name(*number),
+                        number: *number,
+                        count: posts.len(),
+                    })
+                    .collect(),
+            };
+            (archive_year, months.into_iter().map(|(_, p)| p).collect())
+        })
+        .collect::<Vec<_>>();
+    archive.sort_by_key(|(archive_year, _)| std::cmp::Reverse(archive_year.year));
+    archive
+}
+
+fn month_name(number: u32) -> String {
+    u8::try_from(number)
+        .ok()
+        .and_then(|n| Month::try_from(n).ok())
+        .map_or_else(|| number.to_string(), |month| month.name().to_string())
+}
+
+/// Frontmatter keys handled by the other `get_*` helpers
+const KNOWN_FIELDS: [&str; 16] = [
+    "title",
+    "description",
+    "slug",
+    "tags",
+    "date",
+    "author",
+    "draft",
+    "category",
+    "series",
+    "series_index",
+    "toc",
+    "redirect_from",
+    "show_in_menu",
+    "menu_order",
+    "template",
+    "lang",
+];
+
+/// Collects every unrecognised frontmatter key, plus the keys nested under
+/// `extra`, so templates can access them as `content.extra.<key>`.
+pub fn get_extra(frontmatter: &Frontmatter) -> HashMap<String, serde_json::Value> {
+    let mut extra = HashMap::new();
+    for (key, value) in frontmatter.iter() {
+        if key == "extra" {
+            if let Value::Object(nested) = value {
+                for (nested_key, nested_value) in nested.iter() {
+                    extra.insert(nested_key.to_string(), to_json_value(nested_value));
+                }
+            }
+        } else if !KNOWN_FIELDS.contains(&key.as_str()) {
+            extra.insert(key.to_string(), to_json_value(value));
+        }
+    }
+    extra
+}
+
+/// Converts a frontmatter value to its `serde_json` equivalent.
+/// - numbers without a fractional part become integers, others stay floats
+/// - arrays and nested objects are converted recursively
+/// - tagged values (e.g. YAML `!tag value`) keep only the inner value
+fn to_json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::String(s) => serde_json::Value::String(s.to_string()),
+        #[allow(clippy::cast_possible_truncation)]
+        Value::Number(n) if n.fract() == 0.0 => serde_json::Value::from(*n as i64),
+        Value::Number(n) => serde_json::Value::from(*n),
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::Array(items) => items.iter().map(to_json_value).collect(),
+        Value::Object(object) => object
+            .iter()
+            .map(|(k, v)| (k.to_string(), to_json_value(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        Value::Tagged(_, inner) => to_json_value(inner),
+    }
+}
+
+pub fn group_by_tags(posts: &[Content]) -> HashMap<String, Vec<Content>> {
     // Create a HashMap to store the tags and the corresponding Content items.
     let mut tag_map: HashMap<String, Vec<Content>> = HashMap::new();
 
@@ -84,36 +427,32 @@ pub fn group_by_tags(posts: Vec<Content>) -> Vec<(String, Vec<Content>)> {
         }
     }
 
-    // Convert the HashMap into a Vec<(String, Vec<Content>)>
-    tag_map.into_iter().collect()
+    tag_map
 }
 
 /// Tries to get `date` from the front-matter metadata, else from filename
 /// Input examples:
 ///   frontmatter = Frontmatter {date: Value("2024-10-10")}
 ///   path = "2024-01-01-myfile.md"
-pub fn get_date(frontmatter: &Frontmatter, path: &Path) -> Option<NaiveDateTime> {
+pub fn get_date(frontmatter: &Frontmatter, path: &Path) -> Result<Option<NaiveDateTime>, String> {
     if let Some(input) = frontmatter.get("date").and_then(|v| v.as_str()) {
-        match try_to_parse_date(input) {
-            Ok(date) => return Some(date),
-            Err(e) => {
-                error!(
-                    "ERROR: Invalid date format {} when parsing {}, {}",
-                    input,
-                    path.display(),
-                    e.to_string()
-                );
-                process::exit(1);
-            }
-        }
+        return try_to_parse_date(input).map(Some).map_err(|e| {
+            format!(
+                "ERROR: Invalid date format {} when parsing {}, {}",
+                input,
+                path.display(),
+                e
+            )
+        });
     }
-    extract_date_from_filename(path)
+    Ok(extract_date_from_filename(path))
 }
 
-/// Tries to parse 3 different date formats or return Error.
-/// input: "2024-01-01 15:40:56" | "2024-01-01 15:40" | "2024-01-01"
+/// Tries to parse 4 different date formats or return Error.
+/// input: "2024-01-01 15:40:56" | "2024-01-01T15:40:56" | "2024-01-01 15:40" | "2024-01-01"
 fn try_to_parse_date(input: &str) -> Result<NaiveDateTime, chrono::ParseError> {
     NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M:%S"))
         .or_else(|_| NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M"))
         .or_else(|_| {
             NaiveDate::parse_from_str(input, "%Y-%m-%d").map(|d| d.and_hms_opt(0, 0, 0).unwrap())
@@ -129,16 +468,30 @@ fn extract_date_from_filename(path: &Path) -> Option<NaiveDateTime> {
         .and_then(|dt| dt.and_hms_opt(0, 0, 0))
 }
 
-pub fn check_for_duplicate_slugs(contents: &Vec<&Content>) -> Result<(), String> {
-    let mut seen = HashSet::new();
+/// Returns every `(slug, first_path, second_path)` collision found
+pub fn check_for_duplicate_slugs(
+    contents: &Vec<&Content>,
+) -> Result<(), Vec<(String, PathBuf, PathBuf)>> {
+    let mut seen: HashMap<&str, &Path> = HashMap::new();
+    let mut collisions = Vec::new();
 
     for content in contents {
-        if !seen.insert(&content.slug) {
-            return Err(content.slug.clone());
+        if let Some(first) = seen.get(content.slug.as_str()) {
+            collisions.push((
+                content.slug.clone(),
+                first.to_path_buf(),
+                content.source_path.clone(),
+            ));
+        } else {
+            seen.insert(&content.slug, &content.source_path);
         }
     }
 
-    Ok(())
+    if collisions.is_empty() {
+        Ok(())
+    } else {
+        Err(collisions)
+    }
 }
 
 pub fn slugify(text: &str) -> String {
@@ -147,3 +500,23 @@ pub fn slugify(text: &str) -> String {
     let slug = re.replace_all(&normalized, "-");
     slug.trim_matches('-').to_string()
 }
+
+/// Slug of the tag, category and series pages. Accents are dropped as the
+/// `slugify` filter the templates link them with does (`résumé` is
+/// `resume`), content slugs keep splitting words on them (`re-sume`).
+pub fn group_slug(text: &str) -> String {
+    slugify(&text.nfd().filter(char::is_ascii).collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_group_slugs_drop_accents() {
+        assert_eq!(slugify("Résumé"), "re-sume");
+        assert_eq!(slugify("Café com Leite"), "cafe-com-leite");
+        assert_eq!(group_slug("Résumé"), "resume");
+        assert_eq!(group_slug("Café com Leite"), "cafe-com-leite");
+    }
+}
@@ -0,0 +1,414 @@
+use crate::cli::Cli;
+use crate::content::Content;
+use crate::feed::escape_xml;
+use crate::site::{load_site_data, Data};
+use chrono::Utc;
+use log::{error, info, warn};
+use regex::{Captures, Regex};
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const CONTAINER_XML: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+<rootfiles>\n\
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n\
+</rootfiles>\n</container>\n";
+
+/// A post rendered as `OEBPS/<slug>.xhtml`
+struct Chapter<'a> {
+    post: &'a Content,
+    file_name: String,
+    xhtml: String,
+}
+
+/// Packages the dated posts, oldest first, as an EPUB3 ebook titled
+/// `name` and written by `default_author` (`--export-epub`). Images from the
+/// media folder used by the posts, and the `epub_cover`, go in the ebook.
+pub fn export_epub(config_path: &Path, input_folder: &Path, output: &Path, cli_args: &Cli) {
+    let (site_data, content_dir) = load_site_data(config_path, input_folder, cli_args);
+    let media_dir = content_dir.join(&site_data.site.media_path);
+    let mut posts = site_data
+        .posts
+        .iter()
+        .filter(|post| post.date.is_some())
+        .collect::<Vec<_>>();
+    posts.sort_by_key(|post| post.date);
+    if posts.is_empty() {
+        error!("No dated posts to export in {}", content_dir.display());
+        process::exit(1);
+    }
+
+    let slugs = posts
+        .iter()
+        .map(|post| post.slug.as_str())
+        .collect::<HashSet<_>>();
+    let mut images = BTreeSet::new();
+    let chapters = posts
+        .iter()
+        .map(|post| {
+            let body = with_title(
+                &post.title,
+                &to_xhtml(&post.html, &site_data, &slugs, &mut images),
+            );
+            Chapter {
+                post,
+                file_name: chapter_file(&post.slug),
+                xhtml: xhtml_document(&post.title, &body, &site_data.site.default_lang),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let cover = site_data.site.epub_cover.as_ref().filter(|cover| {
+        let exists = media_dir.join(cover).is_file();
+        if !exists {
+            warn!("Cover {} not found in {}", cover, media_dir.display());
+        }
+        exists
+    });
+    if let Some(cover) = cover {
+        images.insert(cover.clone());
+    }
+    let images = images
+        .into_iter()
+        .filter(|image| {
+            let exists = media_dir.join(image).is_file();
+            if !exists {
+                warn!("Image {} not found in {}", image, media_dir.display());
+            }
+            exists
+        })
+        .collect::<Vec<_>>();
+
+    let mut files = vec![
+        ("META-INF/container.xml".to_string(), CONTAINER_XML.into()),
+        (
+            "OEBPS/content.opf".to_string(),
+            content_opf(&site_data, &chapters, &images, cover).into_bytes(),
+        ),
+        (
+            "OEBPS/toc.ncx".to_string(),
+            toc_ncx(&site_data, &chapters).into_bytes(),
+        ),
+        (
+            "OEBPS/nav.xhtml".to_string(),
+            nav_xhtml(&site_data, &chapters).into_bytes(),
+        ),
+    ];
+    if let Some(cover) = cover {
+        let body = format!(
+            "<img src=\"media/{}\" alt=\"{}\" />",
+            escape_xml(cover),
+            escape_xml(&site_data.site.name)
+        );
+        let xhtml = xhtml_document(&site_data.site.name, &body, &site_data.site.default_lang);
+        files.push(("OEBPS/cover.xhtml".to_string(), xhtml.into_bytes()));
+    }
+    for chapter in &chapters {
+        files.push((
+            format!("OEBPS/{}", chapter.file_name),
+            chapter.xhtml.clone().into_bytes(),
+        ));
+    }
+    for image in &images {
+        match fs::read(media_dir.join(image)) {
+            Ok(bytes) => files.push((format!("OEBPS/media/{image}"), bytes)),
+            Err(e) => warn!("Unable to read {}: {}", image, e),
+        }
+    }
+
+    if let Err(e) = write_epub(output, &files) {
+        error!("Failed to write {}: {}", output.display(), e);
+        process::exit(1);
+    }
+    info!("Exported {} posts to {}", chapters.len(), output.display());
+}
+
+/// Writes the `mimetype` first and uncompressed, as readers expect, then
+/// the other files compressed.
+fn write_epub(output: &Path, files: &[(String, Vec<u8>)]) -> zip::result::ZipResult<()> {
+    let mut zip = ZipWriter::new(File::create(output)?);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, bytes) in files {
+        zip.start_file(name.as_str(), deflated)?;
+        zip.write_all(bytes)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// Makes a post html valid XHTML: start tags with quoted attribute values
+/// and void elements closed, `&nbsp;` as a numeric entity, media images
+/// pointing inside the ebook and links to other exported posts pointing to
+/// their chapter. The media images used are added to `images`.
+fn to_xhtml(
+    html: &str,
+    site_data: &Data,
+    slugs: &HashSet<&str>,
+    images: &mut BTreeSet<String>,
+) -> String {
+    let tag = Regex::new(
+        r#"<([a-zA-Z][a-zA-Z0-9-]*)((?:\s+[^\s"'/=>]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'>]+))?)*)\s*/?>"#,
+    )
+    .unwrap();
+    let attribute =
+        Regex::new(r#"([^\s"'/=>]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#).unwrap();
+    let xhtml = tag.replace_all(html, |cap: &Captures| {
+        let mut element = format!("<{}", &cap[1]);
+        for attr in attribute.captures_iter(&cap[2]) {
+            // html allows `allowfullscreen` or `value=1`, xml does not
+            let value = attr
+                .get(2)
+                .or_else(|| attr.get(3))
+                .or_else(|| attr.get(4))
+                .map_or(String::new(), |m| m.as_str().replace('"', "&quot;"));
+            let _ = write!(element, " {}=\"{value}\"", &attr[1]);
+        }
+        let void = matches!(
+            cap[1].to_lowercase().as_str(),
+            "area" | "br" | "col" | "embed" | "hr" | "img" | "input" | "source" | "track" | "wbr"
+        );
+        element.push_str(if void { " />" } else { ">" });
+        element
+    });
+    let xhtml = xhtml.replace("&nbsp;", "&#160;");
+
+    let media = Regex::new(&format!(
+        r#"src="(?:\./|/)?{}/([^"]+)""#,
+        regex::escape(&site_data.site.media_path)
+    ))
+    .unwrap();
+    let xhtml = media.replace_all(&xhtml, |cap: &Captures| {
+        images.insert(cap[1].to_string());
+        format!("src=\"media/{}\"", &cap[1])
+    });
+
+    let links = Regex::new(r##"href="\./([^"#]+)\.html(#[^"]*)?""##).unwrap();
+    links
+        .replace_all(&xhtml, |cap: &Captures| {
+            if slugs.contains(&cap[1]) {
+                let anchor = cap.get(2).map_or("", |m| m.as_str());
+                format!("href=\"{}{anchor}\"", chapter_file(&cap[1]))
+            } else {
+                cap[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+/// Starts the chapter with its title, the html only has it when it came from
+/// a `# heading` instead of the frontmatter
+fn with_title(title: &str, body: &str) -> String {
+    if body.trim_start().starts_with("<h1") {
+        body.to_string()
+    } else {
+        format!("<h1>{}</h1>\n{body}", escape_xml(title))
+    }
+}
+
+/// Chapters are flat in `OEBPS/`, a nested `permalink` joins its folders
+/// with `-`
+fn chapter_file(slug: &str) -> String {
+    format!("{}.xhtml", slug.replace('/', "-"))
+}
+
+fn xhtml_document(title: &str, body: &str, lang: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+        <html xmlns=\"http://www.w3.org/1999/xhtml\" \
+        xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{lang}\" lang=\"{lang}\">\n\
+        <head><meta charset=\"UTF-8\" /><title>{}</title></head>\n\
+        <body>\n{body}\n</body>\n</html>\n",
+        escape_xml(title),
+    )
+}
+
+/// The site url, or a name based URN when there is none
+fn book_identifier(site_data: &Data) -> String {
+    if site_data.site.url.is_empty() {
+        format!(
+            "urn:marmite:{}",
+            crate::content::slugify(&site_data.site.name)
+        )
+    } else {
+        site_data.site.url.clone()
+    }
+}
+
+fn media_type(file_name: &str) -> &'static str {
+    match Path::new(file_name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    }
+}
+
+fn content_opf(
+    site_data: &Data,
+    chapters: &[Chapter],
+    images: &[String],
+    cover: Option<&String>,
+) -> String {
+    let site = &site_data.site;
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+        <item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\n",
+    );
+    let mut spine = String::new();
+    let mut meta = String::new();
+    if cover.is_some() {
+        manifest.push_str(
+            "<item id=\"cover\" href=\"cover.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+        );
+        spine.push_str("<itemref idref=\"cover\"/>\n");
+    }
+    for (index, image) in images.iter().enumerate() {
+        let properties = if cover == Some(image) {
+            let _ = writeln!(meta, "<meta name=\"cover\" content=\"image-{index}\"/>");
+            " properties=\"cover-image\""
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            manifest,
+            "<item id=\"image-{index}\" href=\"media/{}\" media-type=\"{}\"{properties}/>",
+            escape_xml(image),
+            media_type(image)
+        );
+    }
+    for (index, chapter) in chapters.iter().enumerate() {
+        let _ = writeln!(
+            manifest,
+            "<item id=\"chapter-{index}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>",
+            escape_xml(&chapter.file_name)
+        );
+        let _ = writeln!(spine, "<itemref idref=\"chapter-{index}\"/>");
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n\
+        <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+        <dc:identifier id=\"book-id\">{}</dc:identifier>\n\
+        <dc:title>{}</dc:title>\n<dc:creator>{}</dc:creator>\n<dc:language>{}</dc:language>\n\
+        <meta property=\"dcterms:modified\">{}</meta>\n{meta}</metadata>\n\
+        <manifest>\n{manifest}</manifest>\n<spine toc=\"ncx\">\n{spine}</spine>\n</package>\n",
+        escape_xml(&book_identifier(site_data)),
+        escape_xml(&site.name),
+        escape_xml(&site.default_author),
+        escape_xml(&site.default_lang),
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+    )
+}
+
+/// EPUB2 table of contents, for older readers
+fn toc_ncx(site_data: &Data, chapters: &[Chapter]) -> String {
+    let mut points = String::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        let _ = writeln!(
+            points,
+            "<navPoint id=\"chapter-{index}\" playOrder=\"{}\">\
+            <navLabel><text>{}</text></navLabel><content src=\"{}\"/></navPoint>",
+            index + 1,
+            escape_xml(&chapter.post.title),
+            escape_xml(&chapter.file_name)
+        );
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">\n\
+        <head><meta name=\"dtb:uid\" content=\"{}\"/></head>\n\
+        <docTitle><text>{}</text></docTitle>\n<navMap>\n{points}</navMap>\n</ncx>\n",
+        escape_xml(&book_identifier(site_data)),
+        escape_xml(&site_data.site.name),
+    )
+}
+
+fn nav_xhtml(site_data: &Data, chapters: &[Chapter]) -> String {
+    let mut items = String::new();
+    for chapter in chapters {
+        let _ = writeln!(
+            items,
+            "<li><a href=\"{}\">{}</a></li>",
+            escape_xml(&chapter.file_name),
+            escape_xml(&chapter.post.title)
+        );
+    }
+    let body = format!("<nav epub:type=\"toc\" id=\"toc\">\n<ol>\n{items}</ol>\n</nav>");
+    xhtml_document(&site_data.site.name, &body, &site_data.site.default_lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(html: &str) -> (String, BTreeSet<String>) {
+        let site_data = Data::new("", Path::new("marmite.yaml"));
+        let slugs = HashSet::from(["second", "2024/06/third"]);
+        let mut images = BTreeSet::new();
+        let xhtml = to_xhtml(html, &site_data, &slugs, &mut images);
+        (xhtml, images)
+    }
+
+    #[test]
+    fn void_elements_are_closed() {
+        let (xhtml, _) = convert("<p>a<br>b<hr/><input type=checkbox></p>");
+        assert_eq!(xhtml, r#"<p>a<br />b<hr /><input type="checkbox" /></p>"#);
+    }
+
+    #[test]
+    fn attributes_get_double_quoted_values() {
+        let (xhtml, _) = convert(r#"<iframe allowfullscreen src='a "b"' width=10></iframe>"#);
+        assert_eq!(
+            xhtml,
+            r#"<iframe allowfullscreen="" src="a &quot;b&quot;" width="10"></iframe>"#
+        );
+        assert_eq!(convert("a&nbsp;b").0, "a&#160;b");
+    }
+
+    #[test]
+    fn media_images_point_inside_the_ebook() {
+        let (xhtml, images) = convert(
+            r#"<img src="./media/a.png"><img src="/media/b/c.jpg"><img src="https://x.org/d.png">"#,
+        );
+        assert_eq!(
+            xhtml,
+            r#"<img src="media/a.png" /><img src="media/b/c.jpg" /><img src="https://x.org/d.png" />"#
+        );
+        assert_eq!(images.into_iter().collect::<Vec<_>>(), ["a.png", "b/c.jpg"]);
+    }
+
+    #[test]
+    fn links_to_exported_posts_point_to_their_chapter() {
+        let (xhtml, _) = convert(
+            r##"<a href="./second.html#part">b</a><a href="./2024/06/third.html">c</a><a href="./about.html">d</a>"##,
+        );
+        assert_eq!(
+            xhtml,
+            r##"<a href="second.xhtml#part">b</a><a href="2024-06-third.xhtml">c</a><a href="./about.html">d</a>"##
+        );
+    }
+
+    #[test]
+    fn chapters_start_with_their_title() {
+        assert_eq!(
+            with_title("A & B", "<p>text</p>"),
+            "<h1>A &amp; B</h1>\n<p>text</p>"
+        );
+        assert_eq!(with_title("Title", "<h1>Title</h1>"), "<h1>Title</h1>");
+    }
+}
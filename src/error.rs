@@ -0,0 +1,34 @@
+use std::fmt;
+use std::io;
+
+/// Errors raised while turning a source file into `Content`
+#[derive(Debug)]
+pub enum BuildError {
+    /// The file could not be read
+    Io(io::Error),
+    /// Frontmatter, date or other metadata could not be parsed
+    Parse(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Io(e) => write!(f, "{e}"),
+            BuildError::Parse(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl From<io::Error> for BuildError {
+    fn from(e: io::Error) -> Self {
+        BuildError::Io(e)
+    }
+}
+
+impl From<String> for BuildError {
+    fn from(e: String) -> Self {
+        BuildError::Parse(e)
+    }
+}
@@ -0,0 +1,170 @@
+use crate::content::Content;
+use crate::site::Data;
+use chrono::Utc;
+use log::{error, info};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Writes `feed.xml`, a RSS 2.0 feed of the latest `pagination` posts
+pub fn generate_rss(site_data: &Data, output_dir: &Path) {
+    let site = &site_data.site;
+    let mut items = String::new();
+    for post in site_data.posts.iter().take(site.pagination) {
+        let _ = write!(
+            items,
+            "<item><title>{}</title><link>{}</link><guid>{}</guid>",
+            escape_xml(&post.title),
+            escape_xml(&content_url(site_data, post)),
+            escape_xml(&content_url(site_data, post)),
+        );
+        if let Some(date) = post.date {
+            let _ = write!(items, "<pubDate>{}</pubDate>", date.and_utc().to_rfc2822());
+        }
+        let _ = write!(items, "<description>{}</description>", cdata(&post.html));
+        for tag in &post.tags {
+            let _ = write!(items, "<category>{}</category>", escape_xml(tag));
+        }
+        items.push_str("</item>\n");
+    }
+    let rss = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <rss version=\"2.0\">\n<channel>\n\
+        <title>{}</title>\n<link>{}</link>\n<description>{}</description>\n\
+        {items}</channel>\n</rss>\n",
+        escape_xml(&site.name),
+        escape_xml(&site.url),
+        escape_xml(&site.tagline),
+    );
+    write_feed(&output_dir.join("feed.xml"), &rss);
+}
+
+/// Writes an Atom 1.0 feed of the latest `pagination` posts to `atom_path`
+pub fn generate_atom(site_data: &Data, output_dir: &Path) {
+    let site = &site_data.site;
+    let site_author = if site.default_author.is_empty() {
+        &site.name
+    } else {
+        &site.default_author
+    };
+    let updated = site_data
+        .posts
+        .iter()
+        .find_map(|post| post.date)
+        .map_or_else(
+            || Utc::now().to_rfc3339(),
+            |date| date.and_utc().to_rfc3339(),
+        );
+    let mut entries = String::new();
+    for post in site_data.posts.iter().take(site.pagination) {
+        let url = escape_xml(&content_url(site_data, post));
+        let post_updated = post
+            .date
+            .map_or_else(|| updated.clone(), |date| date.and_utc().to_rfc3339());
+        let _ = writeln!(
+            entries,
+            "<entry><id>{url}</id><title>{}</title><link href=\"{url}\"/>\
+            <updated>{post_updated}</updated><author><name>{}</name></author>\
+            <content type=\"html\">{}</content></entry>",
+            escape_xml(&post.title),
+            escape_xml(post.author.as_deref().unwrap_or(site_author)),
+            escape_xml(&post.html),
+        );
+    }
+    let atom = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+        <id>{}</id>\n<title>{}</title>\n<subtitle>{}</subtitle>\n\
+        <link rel=\"self\" href=\"{}\"/>\n<updated>{updated}</updated>\n\
+        {entries}</feed>\n",
+        escape_xml(&site_url(site_data, "")),
+        escape_xml(&site.name),
+        escape_xml(&site.tagline),
+        escape_xml(&site_url(site_data, &site.atom_path)),
+    );
+    let atom_file = output_dir.join(&site.atom_path);
+    if let Some(parent) = atom_file.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    write_feed(&atom_file, &atom);
+}
+
+/// Writes `feed.json`, a JSON Feed 1.1 of the latest `pagination` posts
+pub fn generate_json_feed(site_data: &Data, output_dir: &Path) {
+    let site = &site_data.site;
+    let items = site_data
+        .posts
+        .iter()
+        .take(site.pagination)
+        .map(|post| {
+            let url = content_url(site_data, post);
+            let mut item = serde_json::json!({
+                "id": url,
+                "url": url,
+                "title": post.title,
+                "content_html": post.html,
+                "date_published": post.date.map(|date| date.and_utc().to_rfc3339()),
+                "tags": post.tags,
+            });
+            if let Some(author) = &post.author {
+                item["authors"] = serde_json::json!([{ "name": author }]);
+            }
+            item
+        })
+        .collect::<Vec<_>>();
+    let json_feed = serde_json::json!({
+        "version": "https://jsonfeed.org/version/1.1",
+        "title": site.name,
+        "home_page_url": site_url(site_data, ""),
+        "feed_url": site_url(site_data, "feed.json"),
+        "description": site.tagline,
+        "items": items,
+    });
+    match serde_json::to_string_pretty(&json_feed) {
+        Ok(json) => write_feed(&output_dir.join("feed.json"), &json),
+        Err(e) => error!("Failed to serialize feed.json: {}", e),
+    }
+}
+
+/// Absolute URL of the content when `site.url` is set, in the `<lang>/`
+/// folder when rendering a language. Relative to the page otherwise.
+pub fn content_url(site_data: &Data, content: &Content) -> String {
+    match &site_data.lang {
+        Some(lang) if !site_data.site.url.is_empty() => {
+            site_url(site_data, &format!("{lang}/{}.html", content.slug))
+        }
+        _ => site_url(site_data, &format!("{}.html", content.slug)),
+    }
+}
+
+/// Joins `path` to `site.url`
+pub fn site_url(site_data: &Data, path: &str) -> String {
+    let base_url = site_data.site.url.trim_end_matches('/');
+    if base_url.is_empty() {
+        path.to_string()
+    } else {
+        format!("{base_url}/{path}")
+    }
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Wraps text in CDATA, splitting any `]]>` found in it
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+fn write_feed(path: &Path, feed: &str) {
+    let filename = path.file_name().unwrap_or_default().to_string_lossy();
+    if let Err(e) = fs::write(path, feed) {
+        error!("Failed to write {}: {}", filename, e);
+    } else {
+        info!("Generated {}", path.display());
+    }
+}
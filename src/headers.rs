@@ -0,0 +1,34 @@
+use crate::site::Data;
+use log::{error, info};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Writes a Netlify `_headers` file with security headers for every page,
+/// no caching for HTML and, when `asset_fingerprint` gives static files
+/// unique names, long-lived caching for them, followed by any
+/// `netlify_headers_extra` lines.
+pub fn generate_netlify_headers(site_data: &Data, output_dir: &Path) {
+    let site = &site_data.site;
+    let mut headers = String::from(
+        "/*\n  X-Frame-Options: DENY\n  X-Content-Type-Options: nosniff\n  \
+        Referrer-Policy: strict-origin-when-cross-origin\n\n\
+        /*.html\n  Cache-Control: no-cache\n",
+    );
+    if site.asset_fingerprint {
+        let _ = write!(
+            headers,
+            "\n/{}/*\n  Cache-Control: public, max-age=31536000, immutable\n",
+            site.static_path
+        );
+    }
+    if !site.netlify_headers_extra.is_empty() {
+        let _ = writeln!(headers, "\n{}", site.netlify_headers_extra.join("\n"));
+    }
+    let headers_path = output_dir.join("_headers");
+    if let Err(e) = fs::write(&headers_path, headers) {
+        error!("Failed to write _headers: {}", e);
+    } else {
+        info!("Generated {}", headers_path.display());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::markdown::unescape_html;
+use lazy_static::lazy_static;
+use log::error;
+use regex::{Captures, Regex};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+/// Replaces the text of every `<code class="language-*">` block with
+/// span-coloured HTML using one of the syntect built-in themes.
+/// Blocks in unknown languages are left untouched.
+pub fn highlight_code_blocks(html: &str, theme_name: &str) -> String {
+    let theme = THEME_SET.themes.get(theme_name).unwrap_or_else(|| {
+        error!(
+            "Unknown syntax_theme '{}', using 'InspiredGitHub'",
+            theme_name
+        );
+        &THEME_SET.themes["InspiredGitHub"]
+    });
+    let code_re =
+        Regex::new(r#"(?s)<pre><code class="language-([^"\s]+)">(.*?)</code></pre>"#).unwrap();
+    code_re
+        .replace_all(html, |caps: &Captures| {
+            let Some(syntax) = SYNTAX_SET.find_syntax_by_token(&caps[1]) else {
+                return caps[0].to_string();
+            };
+            let code = unescape_html(&caps[2]);
+            let mut highlighter = HighlightLines::new(syntax, theme);
+            let mut highlighted = String::new();
+            for line in LinesWithEndings::from(&code) {
+                let Ok(regions) = highlighter.highlight_line(line, &SYNTAX_SET) else {
+                    return caps[0].to_string();
+                };
+                match styled_line_to_highlighted_html(&regions, IncludeBackground::No) {
+                    Ok(line_html) => highlighted.push_str(&line_html),
+                    Err(_) => return caps[0].to_string(),
+                }
+            }
+            let background = theme
+                .settings
+                .background
+                .map(|c| {
+                    format!(
+                        r#" style="background-color:#{:02x}{:02x}{:02x};""#,
+                        c.r, c.g, c.b
+                    )
+                })
+                .unwrap_or_default();
+            format!(
+                r#"<pre{background}><code class="language-{}">{highlighted}</code></pre>"#,
+                &caps[1]
+            )
+        })
+        .to_string()
+}
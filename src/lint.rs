@@ -0,0 +1,369 @@
+use crate::cli::Cli;
+use crate::site::{content_dir, Data};
+use log::{error, info, warn};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use walkdir::WalkDir;
+
+/// The byte order mark some Windows editors write at the start of UTF-8 files
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+struct Violation {
+    line: usize,
+    rule: &'static str,
+    description: String,
+}
+
+/// Checks the markdown files of the content folder with a subset of the
+/// markdownlint rules (`--lint-markdown`), printing `<file>:<line> MD0NN
+/// description` for each violation. With `--fix` trailing spaces and
+/// trailing blank lines are removed first. Exits with an error when
+/// violations remain.
+pub fn lint_markdown(config_path: &Path, input_folder: &Path, cli_args: &Cli) {
+    let fix = cli_args.fix;
+    let files = content_files(config_path, input_folder, &["md"], cli_args);
+    let (mut remaining, mut fixed) = (0, 0);
+    for file in &files {
+        let Ok(mut text) = fs::read_to_string(file) else {
+            error!("Unable to read {}", file.display());
+            remaining += 1;
+            continue;
+        };
+        if fix {
+            let fixed_text = fix_markdown(&text);
+            if fixed_text != text {
+                if let Err(e) = fs::write(file, &fixed_text) {
+                    error!("Unable to write {}: {}", file.display(), e);
+                } else {
+                    info!("Fixed {}", file.display());
+                    fixed += 1;
+                    text = fixed_text;
+                }
+            }
+        }
+        for violation in lint(&text) {
+            println!(
+                "{}:{} {} {}",
+                file.display(),
+                violation.line,
+                violation.rule,
+                violation.description
+            );
+            remaining += 1;
+        }
+    }
+
+    if fix {
+        info!("Fixed {} files", fixed);
+    }
+    if remaining > 0 {
+        error!(
+            "Found {} markdown violations in {} files",
+            remaining,
+            files.len()
+        );
+        process::exit(1);
+    }
+    info!("No markdown violations in {} files", files.len());
+}
+
+/// Checks that the markdown and notebook files of the content folder are
+/// valid UTF-8 and have no byte order mark, which hides the frontmatter from
+/// the parser (`--check-encoding`). With `--fix` the marks are removed.
+/// Exits with an error when any file has a mark or invalid UTF-8 left.
+pub fn check_encoding(config_path: &Path, input_folder: &Path, cli_args: &Cli) {
+    let fix = cli_args.fix;
+    let files = content_files(config_path, input_folder, &["md", "ipynb"], cli_args);
+    let mut problems = 0;
+    for file in &files {
+        let Ok(bytes) = fs::read(file) else {
+            error!("Unable to read {}", file.display());
+            problems += 1;
+            continue;
+        };
+        let has_bom = bytes.starts_with(UTF8_BOM);
+        if has_bom {
+            if fix {
+                match fs::write(file, &bytes[UTF8_BOM.len()..]) {
+                    Ok(()) => info!("Removed the byte order mark of {}", file.display()),
+                    Err(e) => {
+                        error!("Unable to write {}: {}", file.display(), e);
+                        problems += 1;
+                    }
+                }
+            } else {
+                warn!(
+                    "{} starts with a UTF-8 byte order mark, remove it with --fix",
+                    file.display()
+                );
+                problems += 1;
+            }
+        }
+        if let Err(e) = std::str::from_utf8(&bytes) {
+            error!(
+                "{} is not valid UTF-8, the first invalid byte is at offset {}",
+                file.display(),
+                e.valid_up_to()
+            );
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        error!(
+            "Found {} encoding problems in {} files",
+            problems,
+            files.len()
+        );
+        process::exit(1);
+    }
+    info!("All {} files are valid UTF-8", files.len());
+}
+
+/// Files of the content folder with one of the `extensions`, sorted by path
+fn content_files(
+    config_path: &Path,
+    input_folder: &Path,
+    extensions: &[&str],
+    cli_args: &Cli,
+) -> Vec<PathBuf> {
+    let site_data = Data::load(config_path, cli_args);
+    WalkDir::new(content_dir(input_folder, &site_data.site))
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| {
+            e.path().is_file()
+                && e.path()
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| extensions.contains(&ext))
+        })
+        .map(walkdir::DirEntry::into_path)
+        .collect()
+}
+
+/// Number of lines taken by a leading YAML or TOML frontmatter, and whether
+/// it sets a `title`
+fn frontmatter(lines: &[&str]) -> (usize, bool) {
+    let Some(fence @ ("---" | "+++")) = lines.first().map(|line| line.trim_end()) else {
+        return (0, false);
+    };
+    let Some(end) = lines
+        .iter()
+        .skip(1)
+        .position(|line| line.trim_end() == fence)
+    else {
+        return (0, false);
+    };
+    let title = Regex::new(r#"^title\s*[:=]"#).unwrap();
+    let has_title = lines[1..=end].iter().any(|line| title.is_match(line));
+    (end + 2, has_title)
+}
+
+fn lint(text: &str) -> Vec<Violation> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let (body_start, has_title) = frontmatter(&lines);
+    let heading = Regex::new(r"^ {0,3}(#{1,6})(\s|$)").unwrap();
+    let fence = Regex::new(r"^ {0,3}(```|~~~)").unwrap();
+
+    let mut violations = Vec::new();
+    let mut report = |line: usize, rule: &'static str, description: String| {
+        violations.push(Violation {
+            line: line + 1,
+            rule,
+            description,
+        });
+    };
+
+    let mut in_fence = false;
+    let mut previous_level = None;
+    let mut first_content = true;
+    // first line and length of each run of blank lines outside code blocks
+    let mut blank_runs: Vec<(usize, usize)> = Vec::new();
+    for (index, line) in lines.iter().enumerate().skip(body_start) {
+        if line.trim().is_empty() && !in_fence {
+            match blank_runs.last_mut() {
+                Some((start, count)) if *start + *count == index => *count += 1,
+                _ => blank_runs.push((index, 1)),
+            }
+        }
+        let trailing = line.len() - line.trim_end_matches(' ').len();
+        // two spaces after some text are a hard line break
+        let line_break = trailing == 2 && !line.trim().is_empty();
+        if trailing > 0 && !line_break {
+            report(
+                index,
+                "MD009",
+                format!("Trailing spaces [Expected: 0 or 2; Actual: {trailing}]"),
+            );
+        }
+        if let Some(column) = line.find('\t') {
+            report(
+                index,
+                "MD010",
+                format!("Hard tabs [Column: {}]", column + 1),
+            );
+        }
+
+        if fence.is_match(line) {
+            in_fence = !in_fence;
+        }
+        let level = if in_fence {
+            None
+        } else {
+            heading.captures(line).map(|cap| cap[1].len())
+        };
+
+        if first_content && !line.trim().is_empty() {
+            first_content = false;
+            if !has_title && level != Some(1) {
+                report(
+                    index,
+                    "MD041",
+                    "First line in a file should be a top-level heading".to_string(),
+                );
+            }
+        }
+
+        let Some(level) = level else {
+            continue;
+        };
+        if let Some(previous) = previous_level {
+            if level > previous + 1 {
+                report(
+                    index,
+                    "MD001",
+                    format!(
+                        "Heading levels should only increment by one level at a time \
+                        [Expected: h{}; Actual: h{level}]",
+                        previous + 1
+                    ),
+                );
+            }
+        }
+        previous_level = Some(level);
+
+        let blank_above = index == body_start || lines[index - 1].trim().is_empty();
+        let blank_below = lines
+            .get(index + 1)
+            .is_none_or(|next| next.trim().is_empty());
+        if !blank_above || !blank_below {
+            let side = match (blank_above, blank_below) {
+                (false, false) => "Above and below",
+                (false, true) => "Above",
+                _ => "Below",
+            };
+            report(
+                index,
+                "MD022",
+                format!("Headings should be surrounded by blank lines [{side}]"),
+            );
+        }
+    }
+
+    for (start, count) in blank_runs {
+        if count > 1 {
+            report(
+                start + 1,
+                "MD012",
+                format!("Multiple consecutive blank lines [Expected: 1; Actual: {count}]"),
+            );
+        }
+    }
+    violations.sort_by_key(|violation| violation.line);
+    violations
+}
+
+/// Removes trailing spaces, keeping two space line breaks, and the blank
+/// lines at the end of the file. Line endings are kept as they are, `\r\n`
+/// included.
+fn fix_markdown(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let lines = text
+        .split_inclusive('\n')
+        .map(|line| {
+            let content = line.trim_end_matches(['\r', '\n']);
+            let ending = &line[content.len()..];
+            let trimmed = content.trim_end_matches(' ');
+            if content.len() - trimmed.len() == 2 && !trimmed.trim().is_empty() {
+                (content, ending)
+            } else {
+                (trimmed, ending)
+            }
+        })
+        .collect::<Vec<_>>();
+    let end = lines.len()
+        - lines
+            .iter()
+            .rev()
+            .take_while(|(content, _)| content.is_empty())
+            .count();
+    let mut fixed = String::with_capacity(text.len());
+    for (content, ending) in &lines[..end] {
+        fixed.push_str(content);
+        fixed.push_str(ending);
+    }
+    if !fixed.ends_with('\n') {
+        fixed.push_str(newline);
+    }
+    fixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(text: &str) -> Vec<(usize, &'static str)> {
+        lint(text)
+            .iter()
+            .map(|violation| (violation.line, violation.rule))
+            .collect()
+    }
+
+    #[test]
+    fn clean_file_has_no_violations() {
+        let text = "---\ntitle: Hello\n---\n\nSome text  \nwith a break.\n\n## Part\n\nEnd.\n";
+        assert!(rules(text).is_empty());
+    }
+
+    #[test]
+    fn each_rule_reports_its_line() {
+        assert_eq!(rules("# Title\n\ntext \n"), [(3, "MD009")]);
+        assert_eq!(rules("# Title\n\n\tindented\n"), [(3, "MD010")]);
+        assert_eq!(rules("text\n"), [(1, "MD041")]);
+        assert_eq!(rules("# Title\n\n### Part\n"), [(3, "MD001")]);
+        assert_eq!(rules("# Title\ntext\n"), [(1, "MD022")]);
+        assert_eq!(rules("# Title\n\n\n\ntext\n"), [(3, "MD012")]);
+    }
+
+    #[test]
+    fn single_blank_lines_are_not_md012() {
+        assert!(rules("# Title\n\ntext\n\n").is_empty());
+        assert_eq!(rules("# Title\n\ntext\n\n\n"), [(5, "MD012")]);
+        assert!(rules("# Title\n\n```\n\n\n```\n").is_empty());
+    }
+
+    #[test]
+    fn fix_removes_trailing_spaces_and_blank_lines() {
+        assert_eq!(
+            fix_markdown("# Title \n\nline  \nend   \n\n\n"),
+            "# Title\n\nline  \nend\n"
+        );
+        assert_eq!(fix_markdown("no newline"), "no newline\n");
+        assert_eq!(fix_markdown(""), "");
+    }
+
+    #[test]
+    fn fix_keeps_crlf_line_endings() {
+        let fixed = fix_markdown("# Title \r\n\r\ntext  \r\nend\r\n\r\n");
+        assert_eq!(fixed, "# Title\r\n\r\ntext  \r\nend\r\n");
+        assert!(rules(&fixed).is_empty());
+        assert_eq!(fix_markdown("# Title\r\n\r\nend"), "# Title\r\n\r\nend\r\n");
+    }
+}
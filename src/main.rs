@@ -1,37 +1,148 @@
 use clap::Parser;
 use env_logger::{Builder, Env};
 use log::{error, info};
-use std::{path::PathBuf, sync::Arc};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+mod assets;
+mod audit;
+mod benchmark;
+mod cache;
 mod cli;
 mod config;
 mod content;
 mod embedded;
+mod epub;
+mod error;
+mod feed;
+mod headers;
+#[cfg(feature = "syntax-highlight")]
+mod highlight;
+mod lint;
 mod markdown;
+mod notebook;
+mod pdf;
+mod redirects;
+mod s3;
+mod seo;
 mod server;
 mod site;
+mod sitemap;
+mod slugs;
+mod stats;
 mod templates;
 mod tera_functions;
+mod translate;
+mod version;
 
 fn main() {
-    let args = cli::Cli::parse();
-    let input_folder = args.input_folder;
-    let output_folder = Arc::new(args.output_folder);
-    let serve = args.serve;
-    let watch = args.watch;
-    let bind_address: &str = args.bind.as_str();
+    let mut args = cli::Cli::parse();
+    // Drafts are always rendered when previewing with the built-in server
+    if args.serve {
+        args.include_drafts = true;
+    }
+    let args = Arc::new(args);
 
-    let config_path = if args.config.starts_with('.') || args.config.starts_with('/') {
-        PathBuf::new().join(args.config)
+    // --benchmark only prints its report, not the logs of every build
+    let level = if args.debug {
+        "debug"
+    } else if args.benchmark {
+        "warn"
     } else {
-        input_folder.join(args.config)
+        "info"
     };
-
-    let env = Env::default().default_filter_or(if args.debug { "debug" } else { "info" });
+    let env = Env::default().default_filter_or(level);
     if let Err(e) = Builder::from_env(env).try_init() {
         error!("Logger already initialized: {}", e);
     }
 
+    // Handle `init` flag, the only one that works without input and output folders
+    if let Some(folder) = &args.init {
+        templates::initialize_project(folder);
+        return;
+    }
+
+    // Handle `version_check` flag
+    if args.version_check {
+        version::check_version();
+        return;
+    }
+
+    // Handle `deploy_s3` flag
+    if let Some([output_dir, bucket]) = args.deploy_s3.as_deref() {
+        s3::deploy_s3(Path::new(output_dir), bucket, args.dry_run);
+        return;
+    }
+
+    let input_folder = args.input_folder.clone().unwrap_or_default();
+    let output_folder = Arc::new(args.output_folder.clone().unwrap_or_default());
+    let config_path = config_path(&args, &input_folder);
+
+    // Handle `lint_markdown` flag
+    if args.lint_markdown {
+        lint::lint_markdown(&config_path, &input_folder, &args);
+        return;
+    }
+
+    // Handle `check_encoding` flag
+    if args.check_encoding {
+        lint::check_encoding(&config_path, &input_folder, &args);
+        return;
+    }
+
+    // Handle `validate_config` flag
+    if args.validate_config {
+        config::validate_config(&config_path);
+        return;
+    }
+
+    // Handle `convert` flag
+    if let Some([source, output]) = args.convert.as_deref() {
+        site::convert_file(&config_path, &input_folder, source, output, &args);
+        return;
+    }
+
+    // Handle `export_epub` flag
+    if let Some(output) = &args.export_epub {
+        epub::export_epub(&config_path, &input_folder, output, &args);
+        return;
+    }
+
+    // Handle `export_pdf` flag
+    if let Some(output) = &args.export_pdf {
+        pdf::export_pdf(&config_path, &input_folder, output, &args);
+        return;
+    }
+
+    // Handle `new_post` flag
+    if let Some(title) = &args.new_post {
+        site::new_post(&config_path, &input_folder, title, &args);
+        return;
+    }
+
+    // Handle `new_page` flag
+    if let Some(title) = &args.new_page {
+        site::new_page(&config_path, &input_folder, title, &args);
+        return;
+    }
+
+    // Handle `translate` flag
+    if let Some([slug, lang]) = args.translate.as_deref() {
+        translate::translate(&config_path, &input_folder, slug, lang, &args);
+        return;
+    }
+
+    // Handle `init_github_pages` flag
+    if args.init_github_pages {
+        templates::initialize_github_pages(
+            &input_folder,
+            args.output_folder.as_deref(),
+            &config_path,
+            &args,
+        );
+        return;
+    }
+
     // Handle `init_templates` flag
     if args.init_templates {
         templates::initialize_templates(&input_folder);
@@ -45,19 +156,143 @@ fn main() {
         return; // Exit early if only initializing theme
     }
 
-    // Generate site content
+    // Handle `stats` flag
+    if args.stats {
+        stats::print_stats(&config_path, &input_folder, &args);
+        return;
+    }
+
+    // Handle `benchmark` flag
+    if args.benchmark {
+        benchmark::run(&config_path, &input_folder, &args);
+        return;
+    }
+
+    // Handle `migrate_slugs` flag
+    if args.migrate_slugs {
+        slugs::migrate_slugs(&config_path, &input_folder, &output_folder, &args);
+        return;
+    }
+
+    // Handle `audit` flag
+    if args.audit {
+        audit::audit(&config_path, &input_folder, &output_folder, &args);
+        return;
+    }
+
+    build_site(&config_path, &input_folder, &output_folder, &args);
+}
+
+/// `--config` paths starting with `.` or `/` are used as given, others are
+/// relative to the input folder
+fn config_path(args: &cli::Cli, input_folder: &Path) -> PathBuf {
+    if args.config.starts_with('.') || args.config.starts_with('/') {
+        PathBuf::new().join(&args.config)
+    } else {
+        input_folder.join(&args.config)
+    }
+}
+
+/// Cleans the output folder with `--clean`, generates the site and serves it
+/// with `--serve`
+fn build_site(
+    config_path: &Path,
+    input_folder: &Path,
+    output_folder: &Arc<PathBuf>,
+    args: &Arc<cli::Cli>,
+) {
+    let bind_address: &str = args.bind.as_str();
+    if args.clean {
+        site::clean_output_folder(input_folder, output_folder, args.yes);
+    }
+
     site::generate(
-        &config_path,
-        &input_folder,
-        &output_folder,
-        watch,
-        serve,
+        config_path,
+        input_folder,
+        output_folder,
+        args.watch,
+        args.serve,
         bind_address,
+        args,
     );
 
-    // Serve the site if the flag was provided
-    if serve && !watch {
+    if args.serve && !args.watch && !args.check {
         info!("Starting built-in HTTP server...");
-        server::start(bind_address, &output_folder);
+        server::start(bind_address, output_folder, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("marmite-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("content")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn builds_with_config_outside_input_folder() {
+        let dir = temp_dir("build-site");
+        let input = dir.join("site");
+        let output = dir.join("public");
+        fs::create_dir_all(input.join("content")).unwrap();
+        fs::write(
+            input.join("content/2024-01-01-hello.md"),
+            "# Hello\n\nFirst post\n",
+        )
+        .unwrap();
+        let config = dir.join("staging.yaml");
+        fs::write(&config, "name: Staging Site\n").unwrap();
+
+        let args = cli::Cli::parse_from([
+            "marmite",
+            input.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--config",
+            config.to_str().unwrap(),
+        ]);
+        build_site(
+            &config_path(&args, &input),
+            &input,
+            &Arc::new(output.clone()),
+            &Arc::new(args),
+        );
+
+        let index = fs::read_to_string(output.join("index.html")).unwrap();
+        assert!(index.contains("Staging Site"));
+        assert!(output.join("hello.html").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_explicit_config_is_an_error() {
+        let dir = temp_dir("missing-config");
+        let output = dir.join("public");
+        let args = cli::Cli::parse_from([
+            "marmite",
+            dir.to_str().unwrap(),
+            output.to_str().unwrap(),
+            "--config",
+            "staging.yaml",
+        ]);
+        let path = config_path(&args, &dir);
+        assert_eq!(path, dir.join("staging.yaml"));
+        assert!(config::read_config(&path, &args).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_default_config_uses_the_defaults() {
+        let dir = temp_dir("default-config");
+        let output = dir.join("public");
+        let args =
+            cli::Cli::parse_from(["marmite", dir.to_str().unwrap(), output.to_str().unwrap()]);
+        let config = config::read_config(&config_path(&args, &dir), &args);
+        assert_eq!(config, Ok(String::new()));
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
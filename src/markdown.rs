@@ -1,48 +1,264 @@
-use crate::content::{get_date, get_description, get_slug, get_tags, get_title, Content};
+use crate::cli::Cli;
+use crate::config::Marmite;
+use crate::content::{
+    expand_permalink, get_author, get_category, get_date, get_description, get_draft, get_extra,
+    get_lang, get_menu_order, get_redirect_from, get_series, get_series_index, get_show_in_menu,
+    get_slug, get_tags, get_template, get_title, get_toc_enabled, slugify, Content, TocEntry,
+};
+use crate::error::BuildError;
+use crate::notebook;
 use crate::site::Data;
+use chrono::Utc;
 use comrak::{markdown_to_html, ComrakOptions};
-use frontmatter_gen::{extract, Frontmatter};
-use regex::Regex;
+use frontmatter_gen::{extract, parser, Format, Frontmatter, Value};
+use log::{debug, info, warn};
+use percent_encoding::percent_decode_str;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-pub fn process_file(path: &Path, site_data: &mut Data) -> Result<(), String> {
-    let content = get_content(path)?;
+/// Adds parsed `content` to posts or pages, unless it is a draft or is
+/// scheduled for the future and the CLI does not ask for those.
+pub fn process_file(mut content: Content, site_data: &mut Data, cli_args: &Cli) {
+    content.slug = expand_permalink(&site_data.site.permalink, &content);
+    let path = &content.source_path;
+    if content.draft {
+        if !cli_args.include_drafts {
+            debug!("Skipping draft {}", path.display());
+            return;
+        }
+        // avoid clashing with the canonical URL once the draft is published
+        content.slug = format!("draft-{}", content.slug);
+    }
+
+    if let Some(date) = content.date {
+        if date > Utc::now().naive_utc() && !cli_args.future {
+            info!(
+                "Skipping {} scheduled for {} (use --future to include it)",
+                path.display(),
+                date
+            );
+            return;
+        }
+    }
 
     if content.date.is_some() {
         site_data.posts.push(content);
     } else {
         site_data.pages.push(content);
     }
-    Ok(())
 }
 
-pub fn get_content(path: &Path) -> Result<Content, String> {
-    let file_content = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    let (frontmatter, markdown) = parse_front_matter(&file_content)?;
-    let html = get_html(markdown);
-    let title = get_title(&frontmatter, markdown);
-    let description = get_description(&frontmatter);
-    let tags = get_tags(&frontmatter);
-    let slug = get_slug(&frontmatter, path);
-    let date = get_date(&frontmatter, path);
-    let extra = frontmatter.get("extra").map(std::borrow::ToOwned::to_owned);
+/// Points `[[Title]]` and `[[label|Title]]` wiki links, which comrak renders
+/// as `<a href="Title" data-wikilink="true">`, to the content with a matching
+/// slug or title. Runs once all the slugs are known, translations resolve to
+/// their own language first.
+pub fn resolve_wiki_links(site_data: &mut Data) {
+    let targets = wiki_link_targets(site_data.posts.iter().chain(&site_data.pages));
+    for content in site_data.posts.iter_mut().chain(&mut site_data.pages) {
+        resolve_content_wiki_links(content, &targets);
+    }
+    for contents in site_data.translations.values_mut() {
+        let mut lang_targets = targets.clone();
+        lang_targets.extend(wiki_link_targets(contents.iter()));
+        for content in contents.iter_mut() {
+            resolve_content_wiki_links(content, &lang_targets);
+        }
+    }
+}
+
+fn wiki_link_targets<'a>(contents: impl Iterator<Item = &'a Content>) -> HashMap<String, String> {
+    let mut targets = HashMap::new();
+    for content in contents {
+        targets.insert(slugify(&content.title), content.slug.clone());
+        targets.insert(content.slug.clone(), content.slug.clone());
+    }
+    targets
+}
+
+fn resolve_content_wiki_links(content: &mut Content, targets: &HashMap<String, String>) {
+    let re = Regex::new(r#"<a href="([^"]*)" data-wikilink="true">"#).unwrap();
+    let mut resolved = Vec::new();
+    let html = re
+        .replace_all(&content.html, |caps: &Captures| {
+            let target = unescape_html(&caps[1]);
+            let target = percent_decode_str(&target).decode_utf8_lossy();
+            if let Some(slug) = targets.get(&slugify(&target)) {
+                resolved.push(slug.clone());
+                format!(r#"<a href="./{slug}.html" data-wikilink="true">"#)
+            } else {
+                warn!(
+                    "Unresolved wiki link [[{}]] in {}",
+                    target,
+                    content.source_path.display()
+                );
+                caps[0].to_string()
+            }
+        })
+        .into_owned();
+    content.html = html;
+    if !resolved.is_empty() {
+        content
+            .links_to
+            .get_or_insert_with(Vec::new)
+            .extend(resolved);
+    }
+}
+
+pub fn get_content(path: &Path, site: &Marmite) -> Result<Content, BuildError> {
+    if path.extension().is_some_and(|ext| ext == "ipynb") {
+        return notebook::get_content(path, site);
+    }
+    let file_content = fs::read_to_string(path)?;
+    let (frontmatter, markdown) = parse_front_matter(&file_content, path)?;
+    let html = get_html(markdown, site);
+    #[cfg(feature = "syntax-highlight")]
+    let html = if site.syntax_highlight {
+        crate::highlight::highlight_code_blocks(&html, &site.syntax_theme)
+    } else {
+        html
+    };
+    build_content(path, &frontmatter, markdown, html, site)
+}
+
+/// Fills `Content` from the `frontmatter` and rendered `html` of a source
+/// file, `markdown` is its raw text used for the title, excerpt and counts.
+pub fn build_content(
+    path: &Path,
+    frontmatter: &Frontmatter,
+    markdown: &str,
+    html: String,
+    site: &Marmite,
+) -> Result<Content, BuildError> {
+    let (html, toc) = if get_toc_enabled(frontmatter) {
+        get_toc(&html)
+    } else {
+        (html, Vec::new())
+    };
+    let title = get_title(frontmatter, markdown);
+    let description = get_description(frontmatter);
+    let excerpt = get_excerpt(frontmatter, markdown, &html, site);
+    let tags = get_tags(frontmatter, &site.tag_aliases);
+    let slug = get_slug(frontmatter, path);
+    let date = get_date(frontmatter, path)?;
+    let author = get_author(frontmatter);
+    let draft = get_draft(frontmatter);
+    let category = get_category(frontmatter);
+    let series = get_series(frontmatter);
+    let series_index = get_series_index(frontmatter);
+    let show_in_menu = get_show_in_menu(frontmatter);
+    let menu_order = get_menu_order(frontmatter);
+    let template = get_template(frontmatter);
+    let redirect_from = get_redirect_from(frontmatter);
+    let lang = get_lang(frontmatter, path);
+    let extra = get_extra(frontmatter);
+    let reading_time_minutes = get_reading_time(&html, site.reading_speed_wpm);
+    let word_count = markdown.split_whitespace().count();
+    let char_count = markdown.trim().chars().count();
     let links_to = get_links_to(&html);
     let back_links = Vec::new(); // will be mutated later
     let content = Content {
         title,
         description,
+        excerpt,
         slug,
         html,
         tags,
         date,
+        author,
+        draft,
+        category,
+        series,
+        series_index,
+        show_in_menu,
+        menu_order,
+        template,
+        toc,
+        redirect_from,
+        lang,
+        reading_time_minutes,
+        word_count,
+        char_count,
         extra,
         links_to,
         back_links,
+        source_path: path.to_path_buf(),
     };
     Ok(content)
 }
 
+/// Collects `<h2>` to `<h4>` headings as `TocEntry` items, reusing the `id`
+/// generated by comrak or adding one to headings without it (e.g. raw HTML).
+fn get_toc(html: &str) -> (String, Vec<TocEntry>) {
+    let heading_re = Regex::new(r"(?s)<h([2-4])([^>]*)>(.*?)</h[2-4]>").unwrap();
+    let id_re = Regex::new(r#"id="([^"]+)""#).unwrap();
+    let mut toc = Vec::new();
+    let html = heading_re
+        .replace_all(html, |caps: &Captures| {
+            let level = caps[1].parse().unwrap_or(2);
+            let (attrs, inner) = (&caps[2], &caps[3]);
+            let text = strip_html_tags(inner);
+            let existing_id = id_re
+                .captures(attrs)
+                .or_else(|| id_re.captures(inner))
+                .map(|id| id[1].to_string());
+            if let Some(id) = existing_id {
+                toc.push(TocEntry { level, text, id });
+                caps[0].to_string()
+            } else {
+                let id = slugify(&text);
+                let heading = format!(r#"<h{level}{attrs} id="{id}">{inner}</h{level}>"#);
+                toc.push(TocEntry { level, text, id });
+                heading
+            }
+        })
+        .to_string();
+    (html, toc)
+}
+
+/// Counts the words of the rendered text, rounding up to at least 1 minute
+fn get_reading_time(html: &str, words_per_minute: u32) -> u32 {
+    let words = strip_html_tags(html).split_whitespace().count();
+    let words = u32::try_from(words).unwrap_or(u32::MAX);
+    words.div_ceil(words_per_minute.max(1)).max(1)
+}
+
+/// Uses `description` from frontmatter if defined, else the text before the
+/// `<!--more-->` marker, else the content cut after `excerpt_length`
+/// characters.
+fn get_excerpt(frontmatter: &Frontmatter, markdown: &str, html: &str, site: &Marmite) -> String {
+    if let Some(Value::String(description)) = frontmatter.get("description") {
+        return description.to_string();
+    }
+    if let Some((before_more, _)) = markdown.split_once("<!--more-->") {
+        return strip_html_tags(&get_html(before_more, site));
+    }
+    truncate_words(&strip_html_tags(html), site.excerpt_length)
+}
+
+/// Cuts `text` at the first word boundary after `length` characters, ending
+/// with `…` when anything was cut
+fn truncate_words(text: &str, length: usize) -> String {
+    let Some((start, _)) = text.char_indices().nth(length) else {
+        return text.to_string();
+    };
+    match text[start..].find(char::is_whitespace) {
+        Some(end) => format!("{}…", text[..start + end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+/// Remove HTML tags, Liquid tags, and Jinja tags, collapsing whitespace
+pub fn strip_html_tags(html: &str) -> String {
+    let re = Regex::new(r"<[^>]*>|(\{\{[^>]*\}\})|(\{%[^>]*%\})").unwrap();
+    re.replace_all(html, "")
+        .replace('\n', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 fn get_links_to(html: &str) -> Option<Vec<String>> {
     let mut result = Vec::new();
     let re = Regex::new(r#"href="\./(.*?)\.html""#).unwrap();
@@ -57,7 +273,7 @@ fn get_links_to(html: &str) -> Option<Vec<String>> {
     Some(result)
 }
 
-pub fn get_html(markdown: &str) -> String {
+pub fn get_html(markdown: &str, site: &Marmite) -> String {
     let mut options = ComrakOptions::default();
     options.render.unsafe_ = true;
     options.render.ignore_empty_links = true;
@@ -76,14 +292,168 @@ pub fn get_html(markdown: &str) -> String {
     options.extension.shortcodes = true;
     options.extension.header_ids = Some("tos-".to_string());
     options.extension.wikilinks_title_before_pipe = true;
+    options.extension.math_dollars = site.math;
 
-    markdown_to_html(markdown, &options)
+    let html = convert_mermaid_blocks(&markdown_to_html(markdown, &options));
+    if site.math {
+        wrap_math(&html)
+    } else {
+        html
+    }
 }
 
-fn parse_front_matter(content: &str) -> Result<(Frontmatter, &str), String> {
+/// Replaces ```` ```mermaid ```` code blocks with `<div class="mermaid">`
+/// containers holding the unescaped diagram source.
+fn convert_mermaid_blocks(html: &str) -> String {
+    const OPEN: &str = r#"<pre><code class="language-mermaid">"#;
+    const CLOSE: &str = "</code></pre>";
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(OPEN) {
+        let diagram = &rest[start + OPEN.len()..];
+        let Some(end) = diagram.find(CLOSE) else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        output.push_str(r#"<div class="mermaid">"#);
+        output.push_str(&unescape_html(&diagram[..end]));
+        output.push_str("</div>");
+        rest = &diagram[end + CLOSE.len()..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Reverts the escaping comrak applies to code block text
+pub fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Turns comrak `$...$` and `$$...$$` spans into the wrappers and delimiters
+/// that KaTeX auto-render picks up on the client side.
+fn wrap_math(html: &str) -> String {
+    let inline_re = Regex::new(r#"(?s)<span data-math-style="inline">(.*?)</span>"#).unwrap();
+    let display_re = Regex::new(r#"(?s)<span data-math-style="display">(.*?)</span>"#).unwrap();
+    let html = inline_re.replace_all(html, r#"<span class="math-inline">\(${1}\)</span>"#);
+    display_re
+        .replace_all(&html, r#"<div class="math-block">\[${1}\]</div>"#)
+        .to_string()
+}
+
+fn parse_front_matter<'a>(content: &'a str, path: &Path) -> Result<(Frontmatter, &'a str), String> {
     if content.starts_with("---") {
+        check_closing_delimiter(content, "---", "+++")?;
         extract(content).map_err(|e| e.to_string())
+    } else if content.starts_with("+++") {
+        parse_toml_front_matter(content)
+    } else if content.starts_with('{') {
+        parse_json_front_matter(content, path)
     } else {
         Ok((Frontmatter::new(), content))
     }
 }
+
+/// Parses a top-level JSON object starting at the first character of the file
+/// and closed by a `}` on a line by itself.
+/// If no closing line is found the whole file is treated as markdown.
+fn parse_json_front_matter<'a>(
+    content: &'a str,
+    path: &Path,
+) -> Result<(Frontmatter, &'a str), String> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        if line.trim_end() == "}" {
+            let raw_frontmatter = &content[..=offset];
+            let markdown = &content[offset + line.len()..];
+            let frontmatter = parser::parse(raw_frontmatter, Format::Json).map_err(|e| {
+                format!(
+                    "ERROR: Invalid JSON frontmatter when parsing {}, {}",
+                    path.display(),
+                    e
+                )
+            })?;
+            return Ok((frontmatter, markdown));
+        }
+        offset += line.len();
+    }
+    Ok((Frontmatter::new(), content))
+}
+
+/// Parses a `+++` delimited TOML block (as used by Hugo) into a `Frontmatter`
+/// so the `get_*` helpers work the same way as for YAML.
+fn parse_toml_front_matter(content: &str) -> Result<(Frontmatter, &str), String> {
+    check_closing_delimiter(content, "+++", "---")?;
+    let end = content[3..]
+        .find("\n+++")
+        .map(|i| i + 3)
+        .ok_or("Missing closing `+++` for TOML frontmatter")?;
+    let raw_frontmatter = content[3..end].trim();
+    let rest = &content[end + 4..];
+    let markdown = rest.find('\n').map_or("", |i| &rest[i + 1..]);
+    let frontmatter = parser::parse(raw_frontmatter, Format::Toml).map_err(|e| e.to_string())?;
+    Ok((frontmatter, markdown))
+}
+
+/// Errors when frontmatter opened with `opener` is closed with `other` instead
+fn check_closing_delimiter(content: &str, opener: &str, other: &str) -> Result<(), String> {
+    let body = &content[opener.len()..];
+    if !body.contains(&format!("\n{opener}")) && body.contains(&format!("\n{other}")) {
+        return Err(format!(
+            "Mismatched frontmatter delimiters: opened with `{opener}` but closed with `{other}`"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn title(frontmatter: &Frontmatter) -> Option<&Value> {
+        frontmatter.get("title")
+    }
+
+    #[test]
+    fn parses_toml_front_matter() {
+        let content = "+++\ntitle = \"Hello\"\ntags = [\"a\", \"b\"]\n+++\n# Body\n";
+        let (frontmatter, markdown) = parse_front_matter(content, Path::new("a.md")).unwrap();
+        assert_eq!(
+            title(&frontmatter),
+            Some(&Value::String("Hello".to_string()))
+        );
+        assert!(frontmatter.get("tags").is_some());
+        assert_eq!(markdown, "# Body\n");
+    }
+
+    #[test]
+    fn parses_json_front_matter() {
+        let content = "{\n  \"title\": \"Hello\",\n  \"date\": \"2024-06-03\"\n}\n# Body\n";
+        let (frontmatter, markdown) = parse_front_matter(content, Path::new("a.md")).unwrap();
+        assert_eq!(
+            title(&frontmatter),
+            Some(&Value::String("Hello".to_string()))
+        );
+        assert_eq!(markdown, "# Body\n");
+
+        let error = parse_front_matter("{\n\"title\": \n}\n", Path::new("a.md")).unwrap_err();
+        assert!(error.starts_with("ERROR: Invalid JSON frontmatter when parsing a.md"));
+    }
+
+    #[test]
+    fn mismatched_delimiters_are_an_error() {
+        for content in [
+            "---\ntitle: Hello\n+++\nBody\n",
+            "+++\ntitle = \"Hello\"\n---\nBody\n",
+        ] {
+            let error = parse_front_matter(content, Path::new("a.md")).unwrap_err();
+            assert!(
+                error.starts_with("Mismatched frontmatter delimiters"),
+                "{error}"
+            );
+        }
+    }
+}
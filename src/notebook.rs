@@ -0,0 +1,69 @@
+use crate::config::Marmite;
+use crate::content::Content;
+use crate::error::BuildError;
+use crate::markdown::{build_content, strip_html_tags};
+use frontmatter_gen::{parser, Format, Frontmatter};
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Builds `Content` from a Jupyter notebook. Its `metadata` is read like a
+/// frontmatter (`title`, `date`, `tags`...) and the body is rendered by
+/// `jupyter nbconvert`, so Jupyter must be installed to build notebooks.
+pub fn get_content(path: &Path, site: &Marmite) -> Result<Content, BuildError> {
+    let notebook: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| BuildError::Parse(format!("Invalid notebook {}: {}", path.display(), e)))?;
+    let frontmatter = match notebook.get("metadata") {
+        Some(metadata @ serde_json::Value::Object(_)) => {
+            parser::parse(&metadata.to_string(), Format::Json).map_err(|e| {
+                BuildError::Parse(format!(
+                    "Invalid notebook metadata in {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?
+        }
+        _ => Frontmatter::new(),
+    };
+    let html = convert_notebook(path)?;
+    let text = strip_html_tags(&html);
+    let mut content = build_content(path, &frontmatter, &text, html, site)?;
+    if frontmatter.get("title").is_none() {
+        if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+            content.title = stem.to_string();
+        }
+    }
+    Ok(content)
+}
+
+fn convert_notebook(path: &Path) -> Result<String, BuildError> {
+    let output = Command::new("jupyter")
+        .args(["nbconvert", "--to", "html", "--stdout"])
+        .arg(path)
+        .output()
+        .map_err(|e| {
+            BuildError::Parse(format!(
+                "Unable to run jupyter nbconvert, is Jupyter installed? {e}"
+            ))
+        })?;
+    if !output.status.success() {
+        return Err(BuildError::Parse(format!(
+            "jupyter nbconvert failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(notebook_body(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Keeps the inside of `<main>` (or `<body>`) of the standalone page
+/// written by nbconvert, dropping its `<html>` shell, styles and scripts.
+fn notebook_body(html: &str) -> String {
+    for tag in ["main", "body"] {
+        let re = Regex::new(&format!(r"(?s)<{tag}[^>]*>(.*)</{tag}>")).unwrap();
+        if let Some(cap) = re.captures(html) {
+            return cap[1].trim().to_string();
+        }
+    }
+    html.to_string()
+}
@@ -0,0 +1,109 @@
+use crate::cli::Cli;
+use crate::server;
+use crate::site::{self, Data};
+use log::{error, info};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::Arc;
+use std::thread;
+use tiny_http::Server;
+
+/// Deletes the temporary build of `--export-pdf` when dropped
+struct TempBuild(Arc<PathBuf>);
+
+impl Drop for TempBuild {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(self.0.as_path());
+    }
+}
+
+/// Builds the site to a temporary folder, serves it on a free local port
+/// and renders its `index.html` to `output` with WeasyPrint
+/// (`--export-pdf`). The build and the server are cleaned up whether or not
+/// the PDF could be rendered.
+pub fn export_pdf(config_path: &Path, input_folder: &Path, output: &Path, cli_args: &Arc<Cli>) {
+    // named after the input folder: a failed build exits the process
+    // without dropping the guard, so its leftover is removed by the next
+    // export of the same site
+    let input = fs::canonicalize(input_folder).unwrap_or_else(|_| input_folder.to_path_buf());
+    let input_hash = format!("{:x}", Sha256::digest(input.to_string_lossy().as_bytes()));
+    let output_folder =
+        Arc::new(std::env::temp_dir().join(format!("marmite-export-pdf-{}", &input_hash[..16])));
+    let _ = fs::remove_dir_all(output_folder.as_path());
+    let build = TempBuild(Arc::clone(&output_folder));
+    site::generate(
+        config_path,
+        input_folder,
+        &output_folder,
+        false,
+        false,
+        &cli_args.bind,
+        cli_args,
+    );
+    let site_path = Data::load(config_path, cli_args).site.site_path;
+
+    let result = render_pdf(&output_folder, &site_path, output, &cli_args.pdf_page_size);
+    drop(build);
+    match result {
+        Ok(()) => info!(
+            "Exported {} to {}",
+            input_folder.display(),
+            output.display()
+        ),
+        Err(e) => {
+            error!("Failed to export {}: {}", output.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+fn render_pdf(
+    output_folder: &Arc<PathBuf>,
+    site_path: &str,
+    output: &Path,
+    page_size: &str,
+) -> Result<(), String> {
+    let stylesheet = output_folder.join("marmite-pdf.css");
+    fs::write(&stylesheet, format!("@page {{ size: {page_size}; }}\n"))
+        .map_err(|e| e.to_string())?;
+
+    let server = Arc::new(Server::http("127.0.0.1:0").map_err(|e| e.to_string())?);
+    let address = server
+        .server_addr()
+        .to_ip()
+        .ok_or("the server has no IP address")?;
+    let serving = {
+        let server = Arc::clone(&server);
+        let output_folder = Arc::clone(output_folder);
+        thread::spawn(move || server::serve(&server, &output_folder, None))
+    };
+    let site_path = if site_path.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", site_path.trim_matches('/'))
+    };
+    let url = format!("http://{address}/{site_path}index.html");
+    info!("Rendering {} with WeasyPrint", url);
+    let rendered = Command::new("weasyprint")
+        .arg("--stylesheet")
+        .arg(&stylesheet)
+        .arg(&url)
+        .arg(output)
+        .output();
+
+    server.unblock();
+    let _ = serving.join();
+
+    let rendered =
+        rendered.map_err(|e| format!("Unable to run weasyprint, is WeasyPrint installed? {e}"))?;
+    if rendered.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "weasyprint failed: {}",
+            String::from_utf8_lossy(&rendered.stderr).trim()
+        ))
+    }
+}
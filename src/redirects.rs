@@ -0,0 +1,191 @@
+use crate::content::group_slug;
+use crate::feed::{content_url, escape_xml};
+use crate::site::Data;
+use chrono::Datelike;
+use log::{debug, error, info, warn};
+use regex::Regex;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path};
+use url::Url;
+
+/// Writes a page redirecting to the content for each of its `redirect_from`
+/// paths, plus `_redirects` (Netlify) and `.htaccess` (Apache) files
+/// listing all of them. Nothing is written when there are no redirects.
+pub fn generate_redirects(site_data: &Data, output_dir: &Path) -> Result<(), String> {
+    let contents = site_data.posts.iter().chain(&site_data.pages);
+    let generated = contents
+        .clone()
+        .map(|c| format!("{}.html", c.slug))
+        .collect::<HashSet<_>>();
+    // redirect rules are absolute paths, so keep the path of `site.url`
+    let base_path = Url::parse(&site_data.site.url)
+        .map(|url| url.path().trim_end_matches('/').to_string())
+        .unwrap_or_default();
+    let base_path = match &site_data.lang {
+        Some(lang) => format!("{base_path}/{lang}"),
+        None => base_path,
+    };
+
+    let mut netlify = String::new();
+    let mut htaccess = String::new();
+    for content in contents {
+        for old_path in &content.redirect_from {
+            let file_name = redirect_file_name(old_path);
+            if file_name.is_empty() {
+                continue;
+            }
+            if !is_relative_path(&file_name) {
+                warn!(
+                    "Not redirecting '{}' to '{}', it leaves the output folder",
+                    old_path, content.slug
+                );
+                continue;
+            }
+            if generated.contains(&file_name) || is_generated_page(site_data, &file_name) {
+                warn!(
+                    "Not redirecting '{}' to '{}', it is the path of a generated page",
+                    old_path, content.slug
+                );
+                continue;
+            }
+
+            let target = if site_data.site.url.is_empty() {
+                // relative to the folder the redirect page is written to
+                let depth = file_name.matches('/').count();
+                format!("{}{}.html", "../".repeat(depth), content.slug)
+            } else {
+                content_url(site_data, content)
+            };
+            let lang = content
+                .lang
+                .as_ref()
+                .unwrap_or(&site_data.site.default_lang);
+            let page = redirect_page(&content.title, &target, lang);
+            if !site_data.check {
+                let page_path = output_dir.join(&file_name);
+                if let Some(parent) = page_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                fs::write(&page_path, page).map_err(|e| e.to_string())?;
+                info!("Generated {}", page_path.display());
+            }
+
+            let from = format!("{base_path}/{}", old_path.trim_matches('/'));
+            let to = format!("{base_path}/{}.html", content.slug);
+            let _ = writeln!(netlify, "{from} {to} 301");
+            let _ = writeln!(htaccess, "Redirect 301 {from} {to}");
+        }
+    }
+
+    if netlify.is_empty() || site_data.check {
+        return Ok(());
+    }
+    for (name, rules) in [("_redirects", netlify), (".htaccess", htaccess)] {
+        let path = output_dir.join(name);
+        if let Err(e) = fs::write(&path, rules) {
+            error!("Failed to write {}: {}", name, e);
+        } else {
+            info!("Generated {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Whether `file_name` stays inside the output folder: no `..`, no root and
+/// no drive prefix
+fn is_relative_path(file_name: &str) -> bool {
+    !file_name.contains('\\')
+        && Path::new(file_name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Writes a page at the paths of the tag and year archive lists before they
+/// moved to `tag/<slug>/index.html` and `archive/<year>/index.html`,
+/// redirecting there so links to `tag-<slug>.html` and `archive-<year>.html`
+/// keep working.
+pub fn generate_moved_pages(site_data: &Data, output_dir: &Path) -> Result<(), String> {
+    if site_data.check {
+        return Ok(());
+    }
+    let tags = site_data.tags.keys().map(|tag| {
+        let slug = group_slug(tag);
+        (
+            tag.clone(),
+            format!("tag-{slug}.html"),
+            format!("tag/{slug}/index.html"),
+        )
+    });
+    let years = site_data
+        .posts
+        .iter()
+        .filter_map(|post| post.date.map(|date| date.year()))
+        .filter(|_| site_data.site.archive)
+        .collect::<BTreeSet<_>>();
+    let years = years.into_iter().map(|year| {
+        (
+            year.to_string(),
+            format!("archive-{year}.html"),
+            format!("archive/{year}/index.html"),
+        )
+    });
+    let lang = site_data
+        .lang
+        .as_ref()
+        .unwrap_or(&site_data.site.default_lang);
+    let contents = site_data
+        .posts
+        .iter()
+        .chain(&site_data.pages)
+        .map(|c| format!("{}.html", c.slug))
+        .collect::<HashSet<_>>();
+    for (title, file_name, target) in tags.chain(years) {
+        // a content at the old path keeps it
+        if contents.contains(&file_name) {
+            continue;
+        }
+        let page_path = output_dir.join(&file_name);
+        fs::write(&page_path, redirect_page(&title, &target, lang)).map_err(|e| e.to_string())?;
+        debug!("Generated {}", page_path.display());
+    }
+    Ok(())
+}
+
+fn redirect_page(title: &str, target: &str, lang: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"{lang}\">\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>{title}</title>\n<link rel=\"canonical\" href=\"{target}\">\n\
+        <meta http-equiv=\"refresh\" content=\"0; url={target}\">\n</head>\n\
+        <body><a href=\"{target}\">{title}</a></body>\n</html>\n",
+        title = escape_xml(title),
+        target = escape_xml(target),
+    )
+}
+
+/// Whether the build writes `file_name` itself (lists, their pagination,
+/// the tag, archive, category and series pages and the pages left at their
+/// old paths), also inside the folder of a language
+fn is_generated_page(site_data: &Data, file_name: &str) -> bool {
+    let name = match file_name.split_once('/') {
+        Some((lang, rest)) if site_data.languages.iter().any(|l| l == lang) => rest,
+        _ => file_name,
+    };
+    let page = Regex::new(
+        r"^((index|pages|tags|archive|categories)(-\d+)?|404|tag-.+|archive-\d+|((tag|category|series)/[^/]+|archive/\d+(/\d+)?)/index(-\d+)?)\.html$",
+    )
+    .unwrap();
+    page.is_match(name)
+}
+
+/// `/old-slug` and `/old-slug/` are written as `old-slug.html`, paths
+/// already ending in `.html` are kept.
+pub fn redirect_file_name(old_path: &str) -> String {
+    let path = old_path.trim_matches('/');
+    if path.is_empty() || path.ends_with(".html") {
+        path.to_string()
+    } else {
+        format!("{path}.html")
+    }
+}
@@ -0,0 +1,94 @@
+use crate::content::Content;
+use crate::feed::{content_url, escape_xml, site_url};
+use crate::markdown::unescape_html;
+use crate::site::Data;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+
+/// Open Graph and Twitter Card values rendered as `og` in content pages,
+/// escaped for the `content` attribute of the `<meta>` tags
+#[derive(Debug, Serialize)]
+pub struct OpenGraph {
+    pub title: String,
+    pub description: String,
+    pub url: String,
+    pub image: Option<String>,
+    pub twitter_card: String,
+    pub twitter_site: Option<String>,
+}
+
+impl OpenGraph {
+    pub fn new(site_data: &Data, content: &Content) -> Self {
+        let site = &site_data.site;
+        let image = first_image(&content.html)
+            .or_else(|| {
+                (!site.card_image.is_empty())
+                    .then(|| format!("{}/{}", site.media_path, site.card_image))
+            })
+            .map(|image| absolute_url(site_data, &image));
+        let twitter_card = if image.is_some() {
+            "summary_large_image"
+        } else {
+            "summary"
+        };
+        Self {
+            title: escape_xml(&content.title),
+            // excerpts taken from the HTML already have entities
+            description: escape_xml(&unescape_html(&content.excerpt)),
+            url: escape_xml(&content_url(site_data, content)),
+            image: image.as_deref().map(escape_xml),
+            twitter_card: twitter_card.to_string(),
+            twitter_site: site.twitter_handle.as_deref().map(escape_xml),
+        }
+    }
+}
+
+/// Returns the `src` of the first `<img>` tag in `html`
+fn first_image(html: &str) -> Option<String> {
+    let re = Regex::new(r#"<img[^>]*\ssrc="([^"]+)""#).unwrap();
+    re.captures(html).map(|cap| cap[1].to_string())
+}
+
+/// Joins relative paths such as `./media/photo.png` to `site.url`
+fn absolute_url(site_data: &Data, path: &str) -> String {
+    if path.starts_with("http://") || path.starts_with("https://") {
+        return path.to_string();
+    }
+    site_url(
+        site_data,
+        path.trim_start_matches("./").trim_start_matches('/'),
+    )
+}
+
+/// Compact schema.org JSON rendered as `json_ld` in content pages, a
+/// `BlogPosting` for posts and a `WebPage` for pages.
+pub fn json_ld(site_data: &Data, content: &Content, is_post: bool) -> String {
+    let url = content_url(site_data, content);
+    let schema = if is_post {
+        let author = content
+            .author
+            .as_ref()
+            .unwrap_or(&site_data.site.default_author);
+        json!({
+            "@context": "https://schema.org",
+            "@type": "BlogPosting",
+            "headline": content.title,
+            "datePublished": content.date.map(|date| date.and_utc().to_rfc3339()),
+            "author": {"@type": "Person", "name": author},
+            "url": url,
+            "description": content.excerpt,
+            "keywords": content.tags.join(", "),
+        })
+    } else {
+        json!({
+            "@context": "https://schema.org",
+            "@type": "WebPage",
+            "name": content.title,
+            "url": url,
+            "description": content.excerpt,
+        })
+    };
+    // keep a `</script>` in a title from closing the script tag
+    schema.to_string().replace("</", "<\\/")
+}
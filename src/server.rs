@@ -1,11 +1,69 @@
-use log::{error, info};
+use log::{debug, error, info};
+use percent_encoding::percent_decode_str;
+use std::fs;
 use std::io::Cursor;
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::{fs::File, path::Path};
-use tiny_http::{Header, Response, Server};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tiny_http::{Header, ReadWrite, Request, Response, Server};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
 
-pub fn start(bind_address: &str, output_folder: &Arc<PathBuf>) {
+/// Injected in served HTML, never written to the output files
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>
+(() => {
+  const socket = new WebSocket(`ws://${window.location.host}/livereload`);
+  socket.onmessage = (event) => {
+    if (JSON.parse(event.data).command === "reload") {
+      window.location.reload();
+    }
+  };
+})();
+</script>"#;
+
+/// Browsers connected to the `/livereload` WebSocket endpoint
+#[derive(Default)]
+pub struct LiveReload {
+    sockets: Mutex<Vec<WebSocket<Box<dyn ReadWrite + Send>>>>,
+}
+
+impl LiveReload {
+    /// Asks every connected browser to reload, forgetting the closed ones
+    pub fn notify(&self) {
+        let mut sockets = self.sockets.lock().unwrap();
+        sockets.retain_mut(|socket| {
+            socket
+                .send(Message::text(r#"{"command":"reload"}"#))
+                .is_ok()
+        });
+        if !sockets.is_empty() {
+            debug!("Sent reload to {} browser(s)", sockets.len());
+        }
+    }
+
+    fn accept(&self, request: Request) {
+        let Some(key) = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+            .map(|h| derive_accept_key(h.value.as_bytes()))
+        else {
+            let response = Response::from_string("Expected a WebSocket request");
+            if let Err(err) = request.respond(response.with_status_code(400)) {
+                error!("Failed to send response: {}", err);
+            }
+            return;
+        };
+        let response = Response::empty(101)
+            .with_header(Header::from_bytes("Sec-WebSocket-Accept", key).unwrap());
+        let stream = request.upgrade("websocket", response);
+        let socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        self.sockets.lock().unwrap().push(socket);
+    }
+}
+
+/// Serves `output_folder`, with live reload when `live_reload` is given
+pub fn start(bind_address: &str, output_folder: &Arc<PathBuf>, live_reload: Option<&LiveReload>) {
     let server = Server::http(bind_address).unwrap();
 
     info!(
@@ -13,8 +71,17 @@ pub fn start(bind_address: &str, output_folder: &Arc<PathBuf>) {
         bind_address
     );
 
+    serve(&server, output_folder, live_reload);
+}
+
+/// Answers the requests of `server` until it is unblocked
+pub fn serve(server: &Server, output_folder: &Path, live_reload: Option<&LiveReload>) {
     for request in server.incoming_requests() {
-        let response = match handle_request(&request, output_folder) {
+        if let (Some(live_reload), "/livereload") = (live_reload, request.url()) {
+            live_reload.accept(request);
+            continue;
+        }
+        let response = match handle_request(&request, output_folder, live_reload.is_some()) {
             Ok(response) => response,
             Err(err) => {
                 error!("Error handling request: {}", err);
@@ -28,64 +95,94 @@ pub fn start(bind_address: &str, output_folder: &Arc<PathBuf>) {
     }
 }
 
-#[allow(clippy::case_sensitive_file_extension_comparisons)]
 fn handle_request(
     request: &tiny_http::Request,
     output_folder: &Path,
+    live_reload: bool,
 ) -> Result<Response<Cursor<Vec<u8>>>, String> {
-    let request_path = match request.url() {
-        "/" => "index.html",
-        url => &url[1..], // Remove the leading '/'
-    };
+    // Remove the query string and the leading '/', then decode `%20` and
+    // the like, so `%2e%2e/` is seen as `../` below
+    let url = request.url().split(['?', '#']).next().unwrap_or_default();
+    let request_path = percent_decode_str(url).decode_utf8_lossy();
+    let request_path = request_path.trim_start_matches('/');
 
-    let file_path = output_folder.join(request_path);
-    let error_path = output_folder.join("404.html");
+    let mut file_path = output_folder.join(request_path);
+    if file_path.is_dir() {
+        file_path.push("index.html");
+    }
+    // Never serve anything outside of the output folder
+    let is_inside = Path::new(request_path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
 
-    if file_path.is_file() {
-        match File::open(&file_path) {
-            Ok(mut file) => {
-                let mut buffer = Vec::new();
-                std::io::copy(&mut file, &mut buffer).map_err(|e| e.to_string())?;
-                info!(
-                    "\"{} {} HTTP/{}\" 200 -",
-                    request.method(),
-                    request_path,
-                    request.http_version()
-                );
-                let mut resp = Response::from_data(buffer);
-                let js_header = Header::from_bytes("Content-Type", "text/javascript").unwrap();
-                if request_path.ends_with(".js") {
-                    resp.add_header(js_header);
-                }
-                Ok(resp)
-            }
-            Err(err) => {
-                error!("Failed to read file {}: {}", file_path.display(), err);
-                Err(format!("Error reading file: {err}"))
-            }
-        }
+    let (file_path, status) = if is_inside && file_path.is_file() {
+        (file_path, 200)
     } else {
-        error!(
-            "\"{} {} HTTP/{}\" 404 -",
-            request.method(),
-            request_path,
-            request.http_version()
-        );
-        render_not_found(&error_path)
+        (output_folder.join("404.html"), 404)
+    };
+    debug!(
+        "\"{} {} HTTP/{}\" {}",
+        request.method(),
+        url,
+        request.http_version(),
+        status
+    );
+
+    if status == 404 && !file_path.is_file() {
+        return Ok(Response::from_string("404 Not Found").with_status_code(404));
     }
+    let mut buffer = fs::read(&file_path).map_err(|err| {
+        error!("Failed to read file {}: {}", file_path.display(), err);
+        format!("Error reading file: {err}")
+    })?;
+    let content_type = get_content_type(&file_path);
+    if live_reload && content_type.starts_with("text/html") {
+        buffer = inject_live_reload(buffer);
+    }
+    Ok(Response::from_data(buffer)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", content_type).unwrap()))
 }
 
-fn render_not_found(error_path: &PathBuf) -> Result<Response<Cursor<Vec<u8>>>, String> {
-    match File::open(error_path) {
-        Ok(mut file) => {
-            let mut buffer = Vec::new();
-            std::io::copy(&mut file, &mut buffer).map_err(|e| e.to_string())?;
-            let resp = Response::from_data(buffer);
-            Ok(resp)
-        }
-        Err(err) => {
-            error!("Error on rendering page 404 - {}", err);
-            Ok(Response::from_string("404 Not Found").with_status_code(404))
-        }
+pub fn get_content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" | "md" => "text/plain; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Adds the live reload script before `</body>`, or at the end of the page
+fn inject_live_reload(html: Vec<u8>) -> Vec<u8> {
+    let mut html = match String::from_utf8(html) {
+        Ok(html) => html,
+        Err(e) => return e.into_bytes(),
+    };
+    match html.rfind("</body>") {
+        Some(position) => html.insert_str(position, LIVE_RELOAD_SCRIPT),
+        None => html.push_str(LIVE_RELOAD_SCRIPT),
     }
+    html.into_bytes()
 }
@@ -1,77 +1,241 @@
-use crate::config::Marmite;
-use crate::content::{check_for_duplicate_slugs, group_by_tags, slugify, Content};
+use crate::assets;
+use crate::cache::{self, BuildCache};
+use crate::cli::Cli;
+use crate::config::{parse_config, read_config, Marmite};
+use crate::content::{
+    check_for_duplicate_slugs, group_by_archive, group_by_category, group_by_series, group_by_tags,
+    group_slug, menu_pages, slugify, ArchiveYear, Content,
+};
 use crate::embedded::{generate_static, EMBEDDED_TERA};
-use crate::markdown::{get_content, process_file};
+use crate::feed;
+use crate::headers;
+use crate::markdown::{get_content, process_file, resolve_wiki_links, strip_html_tags};
+use crate::redirects;
+use crate::seo::{self, OpenGraph};
 use crate::server;
+use crate::sitemap;
+use crate::slugs;
 use crate::tera_functions::UrlFor;
-use chrono::Datelike;
-use fs_extra::dir::{copy as dircopy, CopyOptions};
 use hotwatch::{Event, EventKind, Hotwatch};
-use log::{debug, error, info};
-use regex::Regex;
+use log::{debug, error, info, warn};
+use rayon::prelude::*;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::io::{self, Write};
 use std::path::Path;
+use std::time::{Duration, Instant};
 use std::{fs, process, sync::Arc, sync::Mutex};
 use tera::{Context, Tera};
 use walkdir::WalkDir;
 
+const KATEX_HEAD: &str = r#"<link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/katex@0.16.11/dist/katex.min.css">
+<script defer src="https://cdn.jsdelivr.net/npm/katex@0.16.11/dist/katex.min.js"></script>
+<script defer src="https://cdn.jsdelivr.net/npm/katex@0.16.11/dist/contrib/auto-render.min.js" onload="renderMathInElement(document.body);"></script>"#;
+const MERMAID_SCRIPTS: &str = r#"<script type="module">
+import mermaid from "https://cdn.jsdelivr.net/npm/mermaid@11/dist/mermaid.esm.min.mjs";
+mermaid.initialize({ startOnLoad: true });
+</script>"#;
+
 #[derive(Serialize, Clone)]
 pub struct Data {
     pub site: Marmite,
     pub posts: Vec<Content>,
     pub pages: Vec<Content>,
+    // Grouped copies of `posts`, kept out of the `site_data` template
+    // variable as cloning them into every page context is costly
+    #[serde(skip)]
+    pub tags: HashMap<String, Vec<Content>>,
+    #[serde(skip)]
+    pub categories: HashMap<String, Vec<Content>>,
+    #[serde(skip)]
+    pub series: HashMap<String, Vec<Content>>,
+    /// Static CSS/JS file names to their fingerprinted names
+    #[serde(skip)]
+    pub asset_map: HashMap<String, String>,
+    /// Render everything but write nothing to disk (`--check`)
+    #[serde(skip)]
+    pub check: bool,
+    /// Contents of `about.fr.md` style files in other languages than
+    /// `default_lang`, by language
+    #[serde(skip)]
+    pub translations: HashMap<String, Vec<Content>>,
+    /// `default_lang` followed by the languages in `translations`
+    #[serde(skip)]
+    pub languages: Vec<String>,
+    /// Language of the `<lang>/` subdirectory being rendered
+    #[serde(skip)]
+    pub lang: Option<String>,
+    /// Text of the config file `site` was loaded from
+    #[serde(skip)]
+    pub config_content: String,
+    #[serde(skip)]
+    pub timings: BuildTimings,
+}
+
+/// Page numbers and links of a paginated list, rendered as `pagination`
+#[derive(Debug, Serialize)]
+pub struct Pagination {
+    /// Number of this page, from 1
+    pub current: usize,
+    pub total: usize,
+    pub items_per_page: usize,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+    pub first_url: String,
+    pub last_url: String,
+    pub pages: Vec<PageLink>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PageLink {
+    pub num: usize,
+    pub url: String,
+    pub is_current: bool,
+}
+
+impl Pagination {
+    /// The urls are relative to the folder of the list, where all its pages are
+    fn new(output_filename: &str, current: usize, total: usize, items_per_page: usize) -> Self {
+        let output_filename = output_filename
+            .rsplit_once('/')
+            .map_or(output_filename, |(_, name)| name);
+        Self {
+            current,
+            total,
+            items_per_page,
+            prev_url: (current > 1).then(|| page_url(output_filename, current - 1)),
+            next_url: (current < total).then(|| page_url(output_filename, current + 1)),
+            first_url: page_url(output_filename, 1),
+            last_url: page_url(output_filename, total),
+            pages: (1..=total)
+                .map(|num| PageLink {
+                    num,
+                    url: page_url(output_filename, num),
+                    is_current: num == current,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `index.html` for the first page of a list, `index-2.html` for the second
+fn page_url(output_filename: &str, num: usize) -> String {
+    if num == 1 {
+        format!("{output_filename}.html")
+    } else {
+        format!("{output_filename}-{num}.html")
+    }
+}
+
+/// Output name of the list of a tag, category or series, `tag/<slug>/index`
+fn group_page(prefix: &str, name: &str) -> String {
+    format!("{prefix}/{}/index", group_slug(name))
+}
+
+/// Output name of the archive of a year, or of one of its months,
+/// `archive/<year>/index` and `archive/<year>/<month>/index`
+fn archive_page(year: i32, month: Option<u32>) -> String {
+    match month {
+        Some(month) => format!("archive/{year}/{month:02}/index"),
+        None => format!("archive/{year}/index"),
+    }
+}
+
+/// Time spent on each phase of the last build, for `--benchmark`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BuildTimings {
+    /// Parsing markdown and grouping contents
+    pub content: Duration,
+    /// Rendering and writing the HTML pages
+    pub render: Duration,
+    /// Copying static files, feeds, sitemap and search index
+    pub io: Duration,
 }
 
 impl Data {
-    pub fn new(config_content: &str) -> Self {
-        let site: Marmite = match serde_yaml::from_str::<Marmite>(config_content) {
+    pub fn new(config_content: &str, config_path: &Path) -> Self {
+        let site = match parse_config(config_content, config_path) {
             Ok(site) => site,
             Err(e) => {
-                error!("Failed to parse config YAML: {}", e);
+                error!("{}", e);
                 process::exit(1);
             }
         };
 
-        Data {
+        let mut data = Data {
             site,
             posts: Vec::new(),
             pages: Vec::new(),
+            tags: HashMap::new(),
+            categories: HashMap::new(),
+            series: HashMap::new(),
+            asset_map: HashMap::new(),
+            check: false,
+            translations: HashMap::new(),
+            languages: Vec::new(),
+            lang: None,
+            config_content: config_content.to_string(),
+            timings: BuildTimings::default(),
+        };
+        data.complete_site();
+        data
+    }
+
+    /// Site data from the config file, exits when a `--config` file can't
+    /// be read
+    pub fn load(config_path: &Path, cli_args: &Cli) -> Self {
+        let config_content = read_config(config_path, cli_args).unwrap_or_else(|e| {
+            error!("{}", e);
+            process::exit(1);
+        });
+        Self::new(&config_content, config_path)
+    }
+
+    /// Loads the config file again if it changed, for `--watch`. A config
+    /// that fails to parse is reported and the previous one is kept.
+    pub fn reload_config(&mut self, config_path: &Path) {
+        let config_content = fs::read_to_string(config_path).unwrap_or_default();
+        if config_content == self.config_content {
+            return;
+        }
+        match parse_config(&config_content, config_path) {
+            Ok(site) => {
+                self.site = site;
+                self.complete_site();
+                self.config_content = config_content;
+                info!("Config reloaded from {}", config_path.display());
+            }
+            Err(e) => error!("Failed to reload config, keeping the previous one: {}", e),
         }
     }
+
+    /// Applies `MARMITE_*` overrides and fills the fields computed from `url`
+    fn complete_site(&mut self) {
+        self.site.apply_env_overrides();
+        self.site.check_values();
+        self.site.feed_url = feed::site_url(self, "feed.xml");
+        self.site.sitemap_url = feed::site_url(self, "sitemap.xml");
+    }
 }
 
 pub fn generate(
-    config_path: &std::path::PathBuf,
+    config_path: &Path,
     input_folder: &std::path::Path,
     output_folder: &Arc<std::path::PathBuf>,
     watch: bool, // New parameter for watching,
     serve: bool, // Is running on server mode
     bind_address: &str,
-) {
-    let config_str = fs::read_to_string(config_path).unwrap_or_else(|e| {
-        debug!(
-            "Unable to read '{}', assuming defaults.: {}",
-            &config_path.display(),
-            e
-        );
-        String::new()
-    });
-    if config_str.is_empty() {
-        info!("Config loaded from: defaults");
-    } else {
-        info!("Config loaded from: {}", config_path.display());
-    }
-    let site_data = Arc::new(Mutex::new(Data::new(&config_str)));
+    cli_args: &Arc<Cli>,
+) -> BuildTimings {
+    let mut data = Data::load(config_path, cli_args);
+    data.check = cli_args.check;
+    let site_data = Arc::new(Mutex::new(data));
 
     // Define the content directory
-    let content_dir = {
-        let site_data = site_data.lock().unwrap();
-        Some(input_folder.join(site_data.site.content_path.clone()))
-    }
-    .filter(|path| path.is_dir()) // Take if exists
-    .unwrap_or_else(|| input_folder.to_path_buf());
-    // Fallback to input_folder if not
+    let content_dir = content_dir(input_folder, &site_data.lock().unwrap().site);
+
+    // Browsers to refresh after each rebuild when serving with --watch
+    let live_reload = Arc::new(server::LiveReload::default());
 
     // Function to trigger site regeneration
     let rebuild_site = {
@@ -79,65 +243,163 @@ pub fn generate(
         let output_folder = Arc::clone(output_folder);
         let input_folder = input_folder.to_path_buf();
         let site_data = site_data.clone();
+        let cli_args = Arc::clone(cli_args);
+        let live_reload = Arc::clone(&live_reload);
 
         move || {
+            let started = Instant::now();
             let mut site_data = site_data.lock().unwrap();
             // cleanup before rebuilding, otherwise we get duplicated slug
             site_data.posts = Vec::new();
             site_data.pages = Vec::new();
-            collect_content(&content_dir, &mut site_data);
 
-            // Detect slug collision
-            detect_slug_collision(&site_data);
+            let site_path = site_data.site.site_path.clone();
+            let output_path = output_folder.join(site_path);
 
-            // Feed back_links
-            collect_back_links(&mut site_data);
+            // Reuse contents parsed by the previous build unless the config or templates changed
+            let templates_dir = input_folder.join(&site_data.site.templates_path);
+            let site_hash = cache::site_hash(&site_data.site, &templates_dir);
+            let state_dir = cache::state_dir(&input_folder);
+            let mut build_cache = if cli_args.no_cache {
+                BuildCache::new(site_hash)
+            } else {
+                BuildCache::load(&state_dir, &site_hash)
+            };
+            let mut errors =
+                collect_content(&content_dir, &mut site_data, &cli_args, &mut build_cache);
+            let skipped = skip_failed_files(&mut errors, &cli_args);
+            split_translations(&mut site_data);
 
-            // Sort posts by date (newest first)
-            site_data.posts.sort_by(|a, b| b.date.cmp(&a.date));
-            // Sort pages on title
-            site_data.pages.sort_by(|a, b| b.title.cmp(&a.title));
+            // Detect slug collision
+            errors.extend(detect_slug_collision(&site_data, &cli_args));
+            if stop_on_errors(&errors, &cli_args) {
+                return;
+            }
+
+            if site_data.site.wiki_links {
+                resolve_wiki_links(&mut site_data);
+            }
+            organize_content(&mut site_data);
+            site_data.timings.content = started.elapsed();
 
             // Create the output directory
-            let site_path = site_data.site.site_path.clone();
-            let output_path = output_folder.join(site_path);
-            if let Err(e) = fs::create_dir_all(&output_path) {
-                error!("Unable to create output directory: {}", e);
-                process::exit(1);
+            if !cli_args.check {
+                if let Err(e) = fs::create_dir_all(&output_path) {
+                    error!("Unable to create output directory: {}", e);
+                    process::exit(1);
+                }
+
+                // Copy static folder if present, before rendering so the
+                // fingerprinted asset names are known by the templates
+                handle_static_artifacts(&input_folder, &site_data, &output_folder, &content_dir);
+                if site_data.site.asset_fingerprint {
+                    site_data.asset_map = assets::fingerprint_assets(
+                        &output_folder.join(&site_data.site.static_path),
+                    );
+                }
             }
 
-            // Initialize Tera templates
-            let tera = initialize_tera(&input_folder, &site_data);
+            // Initialize Tera templates and render them
+            let render_started = Instant::now();
+            match initialize_tera(&input_folder, &site_data) {
+                Ok(tera) => {
+                    if let Err(e) = render_templates(&content_dir, &site_data, &tera, &output_path)
+                        .and_then(|()| {
+                            render_translations(&content_dir, &site_data, &tera, &output_path)
+                        })
+                    {
+                        errors.push(format!("Failed to render templates: {e}"));
+                    }
+                }
+                Err(e) => errors.push(e),
+            }
+            site_data.timings.render = render_started.elapsed();
 
-            // Render templates
-            if let Err(e) = render_templates(&content_dir, &site_data, &tera, &output_path) {
-                error!("Failed to render templates: {}", e);
-                process::exit(1);
+            if cli_args.check {
+                print_check_summary(&site_data, &errors);
+                return;
+            }
+            if stop_on_errors(&errors, &cli_args) {
+                return;
             }
 
-            // Copy static folder if present
-            handle_static_artifacts(&input_folder, &site_data, &output_folder, &content_dir);
+            assets::rewrite_asset_references(
+                &output_path,
+                &site_data.site.static_path,
+                &site_data.asset_map,
+            );
+
+            feed::generate_rss(&site_data, &output_path);
+            if site_data.site.atom_feed {
+                feed::generate_atom(&site_data, &output_path);
+            }
+            if site_data.site.json_feed {
+                feed::generate_json_feed(&site_data, &output_path);
+            }
+            if site_data.site.sitemap {
+                sitemap::generate_sitemap(&site_data, &output_path);
+            }
+            if site_data.site.robots_txt {
+                sitemap::generate_robots_txt(&site_data, &output_path);
+            }
+            if site_data.site.netlify_headers {
+                headers::generate_netlify_headers(&site_data, &output_path);
+            }
+            build_cache.save(&state_dir);
+            slugs::write_registry(&site_data, &state_dir);
 
             if site_data.site.enable_search {
                 generate_search_index(&site_data, &output_folder);
             }
 
+            let timings = &mut site_data.timings;
+            timings.io = started
+                .elapsed()
+                .saturating_sub(timings.content + timings.render);
             info!("Site generated at: {}/", output_folder.display());
+            live_reload.notify();
+            if cli_args.notify_send {
+                send_notification(&format!(
+                    "Site rebuilt in {}ms",
+                    started.elapsed().as_millis()
+                ));
+            }
+            // the files that failed to parse fail the build, except under
+            // --watch which keeps serving the rest of the site
+            if skipped > 0 && !cli_args.watch {
+                error!(
+                    "Site generated without {} files that failed to parse",
+                    skipped
+                );
+                process::exit(1);
+            }
         }
     };
 
     // Initial site generation
     rebuild_site();
+    let timings = site_data.lock().unwrap().timings;
 
     // If watch flag is enabled, start hotwatch
-    if watch {
+    if watch && !cli_args.check {
         let mut hotwatch = Hotwatch::new().expect("Failed to initialize hotwatch!");
 
-        // Watch the input folder for changes
+        // Watch the input folder for changes, but not the files the build
+        // writes to it
+        let config_path = config_path.to_path_buf();
+        let state_dir = cache::state_dir(input_folder);
         hotwatch
             .watch(input_folder, move |event: Event| match event.kind {
+                _ if event.paths.iter().all(|path| path.starts_with(&state_dir)) => {}
                 EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
                     info!("Change detected. Rebuilding site...");
+                    if event
+                        .paths
+                        .iter()
+                        .any(|path| path.file_name() == config_path.file_name())
+                    {
+                        site_data.lock().unwrap().reload_config(&config_path);
+                    }
                     rebuild_site();
                 }
                 _ => {}
@@ -149,16 +411,314 @@ pub fn generate(
         // Keep the thread alive for watching
         if serve {
             info!("Starting built-in HTTP server...");
-            server::start(bind_address, output_folder);
+            server::start(bind_address, output_folder, Some(&live_reload));
         } else {
             loop {
-                std::thread::sleep(std::time::Duration::from_secs(1));
+                std::thread::sleep(Duration::from_secs(1));
             }
         }
     }
+    timings
+}
+
+/// `content_path` inside the input folder, or the input folder itself when
+/// there is no such directory
+pub fn content_dir(input_folder: &Path, site: &Marmite) -> std::path::PathBuf {
+    Some(input_folder.join(&site.content_path))
+        .filter(|path| path.is_dir())
+        .unwrap_or_else(|| input_folder.to_path_buf())
+}
+
+/// Parses the contents of the input folder as a build does, without
+/// rendering or writing anything, for the exports of the whole site.
+pub fn load_site_data(
+    config_path: &Path,
+    input_folder: &Path,
+    cli_args: &Cli,
+) -> (Data, std::path::PathBuf) {
+    let mut site_data = Data::load(config_path, cli_args);
+    let content_dir = content_dir(input_folder, &site_data.site);
+    let mut build_cache = BuildCache::new(String::new());
+    let mut errors = collect_content(&content_dir, &mut site_data, cli_args, &mut build_cache);
+    split_translations(&mut site_data);
+    errors.extend(detect_slug_collision(&site_data, cli_args));
+    if stop_on_errors(&errors, cli_args) {
+        process::exit(1);
+    }
+    if site_data.site.wiki_links {
+        resolve_wiki_links(&mut site_data);
+    }
+    organize_content(&mut site_data);
+    (site_data, content_dir)
+}
+
+/// Logs the files that failed to parse and returns how many, they are left
+/// out of the site and the build goes on, to exit with 1 once it finishes.
+/// `--check` keeps them to report at the end.
+fn skip_failed_files(errors: &mut Vec<String>, cli_args: &Cli) -> usize {
+    if cli_args.check {
+        return 0;
+    }
+    if cli_args.notify_send && !errors.is_empty() {
+        send_notification(&format!("Skipped {} files: {}", errors.len(), errors[0]));
+    }
+    let skipped = errors.len();
+    for e in errors.drain(..) {
+        error!("{}", e);
+    }
+    skipped
+}
+
+/// Logs the errors and aborts the build, unless running with `--check`
+/// where errors are collected and reported at the end. Under `--watch` it
+/// returns true instead, so the rebuild stops and the watcher keeps running.
+fn stop_on_errors(errors: &[String], cli_args: &Cli) -> bool {
+    if cli_args.check || errors.is_empty() {
+        return false;
+    }
+    for e in errors {
+        error!("{}", e);
+    }
+    if cli_args.notify_send {
+        send_notification(&format!("Build failed: {}", errors[0]));
+    }
+    if cli_args.watch {
+        error!("Build failed, waiting for changes");
+        return true;
+    }
+    process::exit(1);
+}
+
+/// Shows a desktop notification with `notify-send`, or `osascript` on macOS
+fn send_notification(body: &str) {
+    let result = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {} with title \"Marmite\"",
+            serde_json::to_string(body).unwrap_or_default()
+        );
+        process::Command::new("osascript")
+            .args(["-e", &script])
+            .status()
+    } else {
+        process::Command::new("notify-send")
+            .args(["Marmite", body])
+            .status()
+    };
+    if let Err(e) = result {
+        warn!("Unable to send a desktop notification: {}", e);
+    }
+}
+
+fn print_check_summary(site_data: &Data, errors: &[String]) {
+    if errors.is_empty() {
+        info!(
+            "Check passed: {} posts and {} pages are valid",
+            site_data.posts.len(),
+            site_data.pages.len()
+        );
+        return;
+    }
+    for e in errors {
+        error!("{}", e);
+    }
+    error!("Check failed with {} error(s)", errors.len());
+    process::exit(1);
+}
+
+/// Removes the output folder asking for confirmation unless `assume_yes`.
+/// Does nothing if it doesn't exist or if it contains the input folder.
+pub fn clean_output_folder(input_folder: &Path, output_folder: &Path, assume_yes: bool) {
+    if !output_folder.is_dir() {
+        return;
+    }
+    if let (Ok(input), Ok(output)) = (input_folder.canonicalize(), output_folder.canonicalize()) {
+        if input.starts_with(&output) {
+            error!(
+                "Refusing to clean '{}' as it contains the input folder",
+                output_folder.display()
+            );
+            process::exit(1);
+        }
+    }
+    if !assume_yes {
+        print!(
+            "Remove '{}' and all its contents? [y/N] ",
+            output_folder.display()
+        );
+        let _ = io::stdout().flush();
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err()
+            || !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+        {
+            info!("Skipping clean of '{}'", output_folder.display());
+            return;
+        }
+    }
+    let removed = WalkDir::new(output_folder)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().is_file())
+        .count();
+    if let Err(e) = fs::remove_dir_all(output_folder) {
+        error!("Unable to clean output directory: {}", e);
+        process::exit(1);
+    }
+    info!(
+        "Removed {} files from '{}/'",
+        removed,
+        output_folder.display()
+    );
+}
+
+/// Writes a draft post named after the slugified `title` to the content folder.
+/// Never overwrites, exits with an error if the file already exists.
+pub fn new_post(config_path: &Path, input_folder: &Path, title: &str, cli_args: &Cli) {
+    // serialized as JSON so quotes and colons are valid YAML
+    let frontmatter = format!(
+        "title: {}\ndate: {}\ntags: []\ndraft: true\n",
+        serde_json::to_string(title).unwrap_or_default(),
+        chrono::Utc::now().format("%Y-%m-%d %H:%M")
+    );
+    new_content_file(config_path, input_folder, title, &frontmatter, cli_args);
+}
+
+/// Like `new_post`, for an undated page shown in the menu
+pub fn new_page(config_path: &Path, input_folder: &Path, title: &str, cli_args: &Cli) {
+    let frontmatter = format!(
+        "title: {}\nshow_in_menu: true\ntemplate: content.html\n",
+        serde_json::to_string(title).unwrap_or_default(),
+    );
+    new_content_file(config_path, input_folder, title, &frontmatter, cli_args);
+}
+
+fn new_content_file(
+    config_path: &Path,
+    input_folder: &Path,
+    title: &str,
+    frontmatter: &str,
+    cli_args: &Cli,
+) {
+    let slug = slugify(title);
+    if slug.is_empty() {
+        error!("Unable to create a file name from title '{}'", title);
+        process::exit(1);
+    }
+    let site_data = Data::load(config_path, cli_args);
+    let content_path = content_dir(input_folder, &site_data.site).join(format!("{slug}.md"));
+    if content_path.exists() {
+        error!(
+            "{} already exists, choose another title or edit it",
+            content_path.display()
+        );
+        process::exit(1);
+    }
+
+    if let Err(e) = fs::write(&content_path, format!("---\n{frontmatter}---\n\n")) {
+        error!("Unable to write {}: {}", content_path.display(), e);
+        process::exit(1);
+    }
+    info!("Created {}", content_path.display());
+}
+
+/// Feeds back links, sorts posts by date (newest first) and pages by title,
+/// then groups posts by tag, category and series.
+fn organize_content(site_data: &mut Data) {
+    collect_back_links(site_data);
+
+    site_data.posts.sort_by_key(|p| std::cmp::Reverse(p.date));
+    site_data.pages.sort_by(|a, b| b.title.cmp(&a.title));
+
+    // keeping the date order, except series ordered by series_index
+    site_data.tags = group_by_tags(&site_data.posts);
+    site_data.categories = group_by_category(&site_data.posts);
+    site_data.series = group_by_series(&site_data.posts);
+}
+
+/// Moves contents in other languages than `default_lang` from posts and
+/// pages to `translations`, so the root site only has the default language.
+fn split_translations(site_data: &mut Data) {
+    let default_lang = site_data.site.default_lang.clone();
+    let mut translations: HashMap<String, Vec<Content>> = HashMap::new();
+    for contents in [&mut site_data.posts, &mut site_data.pages] {
+        let (translated, kept): (Vec<_>, Vec<_>) = std::mem::take(contents)
+            .into_iter()
+            .partition(|c| c.lang.as_ref().is_some_and(|lang| *lang != default_lang));
+        *contents = kept;
+        for content in translated {
+            let lang = content.lang.clone().unwrap_or_default();
+            translations.entry(lang).or_default().push(content);
+        }
+    }
+    let mut languages = translations.keys().cloned().collect::<Vec<_>>();
+    languages.sort();
+    languages.insert(0, default_lang);
+    site_data.languages = languages;
+    site_data.translations = translations;
 }
 
-fn collect_back_links(site_data: &mut std::sync::MutexGuard<'_, Data>) {
+/// Site data rendered into the `<lang>/` subdirectory
+fn language_data(site_data: &Data, lang: &str) -> Data {
+    let mut data = Data {
+        site: site_data.site.clone(),
+        posts: Vec::new(),
+        pages: Vec::new(),
+        tags: HashMap::new(),
+        categories: HashMap::new(),
+        series: HashMap::new(),
+        asset_map: site_data.asset_map.clone(),
+        check: site_data.check,
+        translations: HashMap::new(),
+        languages: site_data.languages.clone(),
+        lang: Some(lang.to_string()),
+        config_content: site_data.config_content.clone(),
+        timings: BuildTimings::default(),
+    };
+    if lang == site_data.site.default_lang {
+        data.posts.clone_from(&site_data.posts);
+        data.pages.clone_from(&site_data.pages);
+        data.tags.clone_from(&site_data.tags);
+        data.categories.clone_from(&site_data.categories);
+        data.series.clone_from(&site_data.series);
+        return data;
+    }
+    for content in site_data.translations.get(lang).into_iter().flatten() {
+        if content.date.is_some() {
+            data.posts.push(content.clone());
+        } else {
+            data.pages.push(content.clone());
+        }
+    }
+    organize_content(&mut data);
+    data
+}
+
+/// Renders every language into `output_dir/<lang>/` when there are
+/// translations, the default language is also kept at the root.
+fn render_translations(
+    content_dir: &Path,
+    site_data: &Data,
+    tera: &Tera,
+    output_dir: &Path,
+) -> Result<(), String> {
+    if site_data.translations.is_empty() {
+        return Ok(());
+    }
+    for lang in &site_data.languages {
+        let lang_dir = output_dir.join(lang);
+        if !site_data.check {
+            fs::create_dir_all(&lang_dir).map_err(|e| e.to_string())?;
+        }
+        render_templates(
+            content_dir,
+            &language_data(site_data, lang),
+            tera,
+            &lang_dir,
+        )?;
+    }
+    Ok(())
+}
+
+fn collect_back_links(site_data: &mut Data) {
     let other_contents = site_data
         .posts
         .clone()
@@ -185,11 +745,22 @@ fn _collect_back_links(contents: &mut [Content], other_contents: &[Content]) {
                 }
             }
         }
+        // newest first, pages have no date and can't be sorted by templates
+        contents[i]
+            .back_links
+            .sort_by_key(|c| std::cmp::Reverse(c.date));
     }
 }
 
-fn collect_content(content_dir: &std::path::PathBuf, site_data: &mut Data) {
-    WalkDir::new(content_dir)
+fn collect_content(
+    content_dir: &std::path::PathBuf,
+    site_data: &mut Data,
+    cli_args: &Cli,
+    cache: &mut BuildCache,
+) -> Vec<String> {
+    // notebooks need Jupyter installed, so they are opt-in
+    let notebooks = site_data.site.notebooks;
+    let paths = WalkDir::new(content_dir)
         .into_iter()
         .filter_map(Result::ok)
         .filter(|e| {
@@ -199,42 +770,87 @@ fn collect_content(content_dir: &std::path::PathBuf, site_data: &mut Data) {
                 .and_then(|ext| ext.to_str())
                 .expect("Could not get file name");
             let file_extension = e.path().extension().and_then(|ext| ext.to_str());
-            e.path().is_file() && file_extension == Some("md") && !file_name.starts_with('_')
+            // 404.md is rendered by handle_404, not listed as a page
+            e.path().is_file()
+                && (file_extension == Some("md") || notebooks && file_extension == Some("ipynb"))
+                && !e
+                    .path()
+                    .components()
+                    .any(|c| c.as_os_str() == ".ipynb_checkpoints")
+                && !file_name.starts_with('_')
+                && file_name != "404.md"
         })
-        .for_each(|entry| {
-            if let Err(e) = process_file(entry.path(), site_data) {
-                error!("Failed to process file {}: {}", entry.path().display(), e);
+        .map(walkdir::DirEntry::into_path)
+        .collect::<Vec<_>>();
+
+    // Parse files in parallel, then merge them on this thread in walk order
+    let site = &site_data.site;
+    let cached = &*cache;
+    let results = paths
+        .par_iter()
+        .map(|path| match cached.get(path) {
+            Some(content) => Ok(content),
+            None => get_content(path, site),
+        })
+        .collect::<Vec<_>>();
+
+    let mut errors = Vec::new();
+    // only keep entries for files that still exist and parsed fine
+    let mut fresh = BuildCache::new(cache.site_hash.clone());
+    for (path, result) in paths.iter().zip(results) {
+        match result {
+            Ok(content) => {
+                fresh.insert(path, &content);
+                process_file(content, site_data, cli_args);
             }
-        });
+            Err(e) => errors.push(format!("Failed to process file {}: {}", path.display(), e)),
+        }
+    }
+    *cache = fresh;
+    errors
 }
 
-fn detect_slug_collision(site_data: &Data) {
-    if let Err(duplicate) = check_for_duplicate_slugs(
-        &site_data
-            .posts
-            .iter()
-            .chain(&site_data.pages)
-            .collect::<Vec<_>>(),
-    ) {
-        error!(
-            "Error: Duplicate slug found: '{}' \
-            - try setting any of `title`, `slug` as a unique text, \
-            or leave both empty so filename will be assumed.",
-            duplicate
-        );
-        process::exit(1);
+fn detect_slug_collision(site_data: &Data, cli_args: &Cli) -> Vec<String> {
+    // translations share the slug of the content they translate
+    let root = site_data.posts.iter().chain(&site_data.pages).collect();
+    let collisions = std::iter::once(root)
+        .chain(
+            site_data
+                .translations
+                .values()
+                .map(|contents| contents.iter().collect()),
+        )
+        .filter_map(|contents| check_for_duplicate_slugs(&contents).err())
+        .flatten()
+        .collect::<Vec<_>>();
+    let mut errors = Vec::new();
+    for (slug, first, second) in collisions {
+        if cli_args.allow_slug_collision {
+            warn!(
+                "Duplicate slug '{}' in {} and {}, keeping the last written",
+                slug,
+                first.display(),
+                second.display()
+            );
+        } else {
+            errors.push(format!(
+                "Error: Duplicate slug found: '{}' in {} and {} \
+                - try setting any of `title`, `slug` as a unique text, \
+                or leave both empty so filename will be assumed.",
+                slug,
+                first.display(),
+                second.display()
+            ));
+        }
     }
+    errors
 }
 
-fn initialize_tera(input_folder: &Path, site_data: &Data) -> Tera {
+fn initialize_tera(input_folder: &Path, site_data: &Data) -> Result<Tera, String> {
     let templates_path = input_folder.join(site_data.site.templates_path.clone());
-    let mut tera = match Tera::new(&format!("{}/**/*.html", templates_path.display())) {
-        Ok(t) => t,
-        Err(e) => {
-            error!("Error loading templates: {}", e);
-            process::exit(1);
-        }
-    };
+    // parse only, so custom templates can extend the embedded ones (e.g. base.html)
+    let mut tera = Tera::parse(&format!("{}/**/*.html", templates_path.display()))
+        .map_err(|e| format!("Error loading templates: {e}"))?;
     tera.autoescape_on(vec![]);
     // the person writing a static site knows what is doing!
     tera.register_function(
@@ -243,28 +859,108 @@ fn initialize_tera(input_folder: &Path, site_data: &Data) -> Tera {
             base_url: site_data.site.url.to_string(),
         },
     );
-    tera.extend(&EMBEDDED_TERA).unwrap();
-    tera
+    tera.extend(&EMBEDDED_TERA).map_err(|e| e.to_string())?;
+    tera.build_inheritance_chains()
+        .map_err(|e| format!("Error loading templates: {e}"))?;
+    Ok(tera)
 }
 
-fn render_templates(
+/// Context of the variables available on every template
+fn global_context(
     content_dir: &Path,
     site_data: &Data,
-    tera: &Tera,
-    output_dir: &Path,
-) -> Result<(), String> {
-    // Build the context of variables that are global on every template
+    archive: &[(ArchiveYear, Vec<Vec<Content>>)],
+) -> Context {
     let mut global_context = Context::new();
     global_context.insert("site_data", &site_data);
     global_context.insert("site", &site_data.site);
     global_context.insert("menu", &site_data.site.menu);
+    global_context.insert("menu_pages", &menu_pages(&site_data.pages));
+    global_context.insert("excerpt_more_text", &site_data.site.excerpt_more_text);
+    let mut categories = site_data.categories.keys().collect::<Vec<_>>();
+    categories.sort();
+    global_context.insert("categories", &categories);
+    global_context.insert("tags", &unique_tags(site_data));
+    global_context.insert("asset_map", &site_data.asset_map);
+    global_context.insert("languages", &site_data.languages);
+    global_context.insert(
+        "current_lang",
+        site_data
+            .lang
+            .as_ref()
+            .unwrap_or(&site_data.site.default_lang),
+    );
+    if site_data.site.archive {
+        let archive_tree = archive.iter().map(|(y, _)| y).collect::<Vec<_>>();
+        global_context.insert("archive_tree", &archive_tree);
+    }
+    if site_data.site.math {
+        global_context.insert("head_extra", KATEX_HEAD);
+    }
+    if site_data.site.mermaid {
+        global_context.insert("body_scripts", MERMAID_SCRIPTS);
+    }
 
-    let hero_fragment = get_html_fragment("_hero.md", content_dir);
+    let hero_fragment = get_html_fragment("_hero.md", content_dir, &site_data.site);
     if !hero_fragment.is_empty() {
         global_context.insert("hero", &hero_fragment);
         debug!("Hero fragment {}", &hero_fragment);
     }
     debug!("Global Context site: {:?}", &site_data.site);
+    global_context
+}
+
+/// Writes `source` rendered with the `content.html` template to `output`,
+/// for `--convert`, using the config and templates of `input_folder`.
+pub fn convert_file(
+    config_path: &Path,
+    input_folder: &Path,
+    source: &Path,
+    output: &Path,
+    cli_args: &Cli,
+) {
+    let site_data = Data::load(config_path, cli_args);
+    let content = get_content(source, &site_data.site).unwrap_or_else(|e| {
+        error!("Failed to convert {}: {}", source.display(), e);
+        process::exit(1);
+    });
+    let tera = initialize_tera(input_folder, &site_data).unwrap_or_else(|e| {
+        error!("{}", e);
+        process::exit(1);
+    });
+
+    let content_dir = input_folder.join(&site_data.site.content_path);
+    let mut context = global_context(&content_dir, &site_data, &[]);
+    context.insert("title", &content.title);
+    context.insert("content", &content);
+    let (Some(output_dir), Some(filename)) = (
+        output.parent(),
+        output.file_name().and_then(|name| name.to_str()),
+    ) else {
+        error!("Invalid output file {}", output.display());
+        process::exit(1);
+    };
+    if let Err(e) = render_html(
+        "content.html",
+        filename,
+        &tera,
+        &context,
+        output_dir,
+        &site_data,
+    ) {
+        error!("Failed to convert {}: {}", source.display(), e);
+        process::exit(1);
+    }
+}
+
+fn render_templates(
+    content_dir: &Path,
+    site_data: &Data,
+    tera: &Tera,
+    output_dir: &Path,
+) -> Result<(), String> {
+    let archive = group_by_archive(&site_data.posts);
+    let global_context = global_context(content_dir, site_data, &archive);
 
     handle_list_page(
         &global_context,
@@ -274,6 +970,7 @@ fn render_templates(
         tera,
         output_dir,
         "index",
+        "list.html",
     )?;
 
     handle_list_page(
@@ -284,18 +981,137 @@ fn render_templates(
         tera,
         output_dir,
         "pages",
+        "list.html",
     )?;
 
     // Render individual content-slug.html from content.html template
     handle_content_pages(site_data, &global_context, tera, output_dir)?;
 
     // Check and guarantees that page 404 was generated even if 404.md is removed
-    handle_404(content_dir, &global_context, tera, output_dir)?;
+    handle_404(content_dir, site_data, &global_context, tera, output_dir)?;
 
     // Render tagged_contents
     handle_tag_pages(output_dir, site_data, &global_context, tera)?;
-    handle_archive_pages(output_dir, site_data, &global_context, tera)?;
+    if site_data.site.archive {
+        handle_archive_pages(output_dir, site_data, &archive, &global_context, tera)?;
+    }
+    handle_category_pages(output_dir, site_data, &global_context, tera)?;
+    handle_series_pages(output_dir, site_data, &global_context, tera)?;
+
+    // Pages pointing old paths from `redirect_from` to their content
+    redirects::generate_redirects(site_data, output_dir)?;
+    redirects::generate_moved_pages(site_data, output_dir)?;
+
+    Ok(())
+}
+
+/// The pages `render_templates` and `render_translations` write for
+/// `site_data`, relative to the output folder with the date of their content
+/// when they have one. `404.html` and the redirect pages are left out.
+pub fn rendered_pages(site_data: &Data) -> Vec<(String, Option<chrono::NaiveDateTime>)> {
+    let mut pages = language_pages(site_data, "");
+    if !site_data.translations.is_empty() {
+        for lang in &site_data.languages {
+            pages.extend(language_pages(
+                &language_data(site_data, lang),
+                &format!("{lang}/"),
+            ));
+        }
+    }
+    pages
+}
+
+fn language_pages(site_data: &Data, prefix: &str) -> Vec<(String, Option<chrono::NaiveDateTime>)> {
+    let per_page = site_data.site.pagination;
+    let mut pages = Vec::new();
+    let mut list_pages = |output_filename: &str, contents: &[Content]| {
+        for num in 1..=contents.len().div_ceil(per_page) {
+            pages.push((format!("{prefix}{}", page_url(output_filename, num)), None));
+        }
+    };
+    list_pages("index", &site_data.posts);
+    list_pages("pages", &site_data.pages);
+    for (tag, contents) in &site_data.tags {
+        list_pages(&group_page("tag", tag), contents);
+    }
+    let archive = group_by_archive(&site_data.posts);
+    if site_data.site.archive {
+        for (archive_year, months) in &archive {
+            let year = archive_year.year;
+            list_pages(&archive_page(year, None), &months.concat());
+            for (archive_month, contents) in archive_year.months.iter().zip(months) {
+                list_pages(&archive_page(year, Some(archive_month.number)), contents);
+            }
+        }
+    }
+    for (category, contents) in &site_data.categories {
+        list_pages(&group_page("category", category), contents);
+    }
+    for (series, contents) in &site_data.series {
+        list_pages(&group_page("series", series), contents);
+    }
+
+    let mut groups = vec!["tags.html", "categories.html"];
+    if site_data.site.archive {
+        groups.push("archive.html");
+    }
+    pages.extend(
+        groups
+            .into_iter()
+            .map(|page| (format!("{prefix}{page}"), None)),
+    );
+    pages.extend(
+        site_data
+            .posts
+            .iter()
+            .chain(&site_data.pages)
+            .filter(|content| content.slug != "404")
+            .map(|content| (format!("{prefix}{}.html", content.slug), content.date)),
+    );
+    pages
+}
 
+/// Mirrors `source` into `output_folder/<source name>/`, skipping files
+/// with the same size and modification time as the copy from a previous
+/// build (the copy keeps the source mtime), and files matching `is_excluded`.
+fn copy_folder(
+    source: &Path,
+    output_folder: &Path,
+    is_excluded: impl Fn(&Path) -> bool,
+) -> io::Result<()> {
+    let destination = output_folder.join(source.file_name().unwrap_or_default());
+    let (mut copied, mut skipped) = (0, 0);
+    for entry in WalkDir::new(source).into_iter().filter_map(Result::ok) {
+        let relative = entry.path().strip_prefix(source).unwrap_or(entry.path());
+        let target = destination.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if is_excluded(entry.path()) {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        let is_unchanged = fs::metadata(&target).is_ok_and(|existing| {
+            existing.len() == metadata.len() && existing.modified().ok() == metadata.modified().ok()
+        });
+        if is_unchanged {
+            skipped += 1;
+            continue;
+        }
+        fs::copy(entry.path(), &target)?;
+        fs::File::options()
+            .write(true)
+            .open(&target)?
+            .set_modified(metadata.modified()?)?;
+        copied += 1;
+    }
+    debug!(
+        "Copied {} files to '{}', {} unchanged",
+        copied,
+        destination.display(),
+        skipped
+    );
     Ok(())
 }
 
@@ -307,13 +1123,26 @@ fn handle_static_artifacts(
 ) {
     let static_source = input_folder.join(site_data.site.static_path.clone());
     if static_source.is_dir() {
-        let mut options = CopyOptions::new();
-        options.overwrite = true; // Overwrite files if they already exist
-
-        if let Err(e) = dircopy(&static_source, &**output_folder, &options) {
+        // sass sources are replaced by the compiled css
+        let scss = site_data.site.scss;
+        if let Err(e) = copy_folder(&static_source, output_folder, |path| {
+            scss && assets::is_sass(path)
+        }) {
             error!("Failed to copy static directory: {}", e);
             process::exit(1);
         }
+        if scss {
+            let errors = assets::compile_sass(
+                &static_source,
+                &output_folder.join(site_data.site.static_path.clone()),
+            );
+            if !errors.is_empty() {
+                for e in errors {
+                    error!("{}", e);
+                }
+                process::exit(1);
+            }
+        }
 
         info!(
             "Copied '{}' to '{}/'",
@@ -327,10 +1156,7 @@ fn handle_static_artifacts(
     // Copy content/media folder if present
     let media_source = content_dir.join(site_data.site.media_path.clone());
     if media_source.is_dir() {
-        let mut options = CopyOptions::new();
-        options.overwrite = true; // Overwrite files if they already exist
-
-        if let Err(e) = dircopy(&media_source, &**output_folder, &options) {
+        if let Err(e) = copy_folder(&media_source, output_folder, |_| false) {
             error!("Failed to copy media directory: {}", e);
             process::exit(1);
         }
@@ -352,6 +1178,11 @@ fn handle_static_artifacts(
         ("favicon.ico", String::new()),
         ("robots.txt", String::new()),
     ];
+    // a generated robots.txt takes precedence over any existing one
+    let custom_files = custom_files
+        .iter()
+        .filter(|(name, _)| !(site_data.site.robots_txt && *name == "robots.txt"))
+        .collect::<Vec<_>>();
     let output_static_destiny = output_folder.join(site_data.site.static_path.clone());
     let possible_sources = [input_folder, content_dir, output_static_destiny.as_path()];
     let mut copied_custom_files = Vec::new();
@@ -382,23 +1213,13 @@ fn handle_static_artifacts(
 }
 
 fn generate_search_index(site_data: &Data, output_folder: &Arc<std::path::PathBuf>) {
-    let remove_html_tags = |html: &str| -> String {
-        // Remove HTML tags, Liquid tags, and Jinja tags
-        let re = Regex::new(r"<[^>]*>|(\{\{[^>]*\}\})|(\{%[^>]*%\})").unwrap();
-        re.replace_all(html, "")
-            .replace('\n', " ")
-            .split_whitespace()
-            .collect::<Vec<_>>()
-            .join(" ")
-    };
-
     let convert_items_to_json = |item: &Content| {
         serde_json::json!({
             "title": item.title,
             "description": item.description,
             "tags": item.tags,
             "slug": item.slug,
-            "html": remove_html_tags(&item.html),
+            "html": strip_html_tags(&item.html),
         })
     };
 
@@ -431,6 +1252,7 @@ fn generate_search_index(site_data: &Data, output_folder: &Arc<std::path::PathBu
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_list_page(
     global_context: &Context,
     title: &str,
@@ -439,78 +1261,51 @@ fn handle_list_page(
     tera: &Tera,
     output_dir: &Path,
     output_filename: &str,
+    template: &str,
 ) -> Result<(), String> {
     let per_page = &site_data.site.pagination;
     let total_content = all_content.len();
-    let total_pages = (total_content + per_page - 1) / per_page;
+    let total_pages = total_content.div_ceil(*per_page);
     for page_num in 0..total_pages {
         let mut context = global_context.clone();
 
         // Slice the content list for this page
         let page_content =
             &all_content[page_num * per_page..(page_num * per_page + per_page).min(total_content)];
+        let pagination = Pagination::new(output_filename, page_num + 1, total_pages, *per_page);
+        let filename = page_url(output_filename, page_num + 1);
 
-        // Set up context for pagination
         context.insert("title", title);
         context.insert("content_list", page_content);
-        context.insert("total_pages", &total_pages);
-        context.insert("per_page", &per_page);
-        context.insert("total_content", &total_content);
-
-        // Determine filename and pagination values
-        let (current_page_number, filename) = if page_num == 0 {
-            (1, format!("{output_filename}.html"))
-        } else {
-            (
-                page_num + 1,
-                format!("{}-{}.html", output_filename, page_num + 1),
-            )
-        };
         context.insert("current_page", &filename);
-        context.insert("current_page_number", &current_page_number);
+        context.insert("pagination", &pagination);
 
-        if page_num > 0 {
-            let prev_page = if page_num == 1 {
-                format!("{output_filename}.html")
-            } else {
-                format!("{output_filename}-{page_num}.html")
-            };
-            context.insert("previous_page", &prev_page);
+        // deprecated, the variables of `pagination` before it existed, kept
+        // for custom templates still using them
+        context.insert("total_pages", &total_pages);
+        context.insert("per_page", per_page);
+        context.insert("total_content", &total_content);
+        context.insert("current_page_number", &pagination.current);
+        if let Some(prev_url) = &pagination.prev_url {
+            context.insert("previous_page", prev_url);
         }
-
-        if page_num < total_pages - 1 {
-            let next_page = format!("{}-{}.html", output_filename, page_num + 2);
-            context.insert("next_page", &next_page);
+        if let Some(next_url) = &pagination.next_url {
+            context.insert("next_page", next_url);
         }
 
         // Debug print
         debug!(
-            "List Context for {}: {:#?} - Pagination({:#?})",
+            "List Context for {}: {:#?} - {:#?}",
             filename,
             page_content
                 .iter()
                 .map(|p| format!("title:{}, slug:{}", p.title, p.slug))
                 .collect::<Vec<_>>(),
-            [
-                "total_pages",
-                "per_page",
-                "total_content",
-                "current_page",
-                "current_page_number",
-                "previous_page",
-                "next_page"
-            ]
-            .iter()
-            .map(|name| format!(
-                "{}:{}",
-                name,
-                context.get(name).unwrap_or(&tera::Value::Null)
-            ))
-            .collect::<Vec<_>>()
+            pagination
         );
 
         // Render the HTML file for this page
-        render_html("list.html", &filename, tera, &context, output_dir)?;
+        render_html(template, &filename, tera, &context, output_dir, site_data)?;
     }
     Ok(())
 }
@@ -521,11 +1316,38 @@ fn handle_content_pages(
     tera: &Tera,
     output_dir: &Path,
 ) -> Result<(), String> {
-    for content in site_data.posts.iter().chain(&site_data.pages) {
+    for (index, content) in site_data.posts.iter().chain(&site_data.pages).enumerate() {
         let mut content_context = global_context.clone();
         content_context.insert("title", &content.title);
         content_context.insert("content", &content);
         content_context.insert("current_page", &format!("{}.html", &content.slug));
+        content_context.insert("og", &OpenGraph::new(site_data, content));
+        let is_post = index < site_data.posts.len();
+        content_context.insert("json_ld", &seo::json_ld(site_data, content, is_post));
+        if is_post {
+            let (prev, next) = adjacent_posts(&site_data.posts, index);
+            if let Some(prev) = prev {
+                content_context.insert("prev_post", prev);
+            }
+            if let Some(next) = next {
+                content_context.insert("next_post", next);
+            }
+        }
+        if let Some(series_posts) = content
+            .series
+            .as_ref()
+            .and_then(|s| site_data.series.get(s))
+        {
+            if let Some(position) = series_posts.iter().position(|p| p.slug == content.slug) {
+                content_context.insert("series_posts", series_posts);
+                if position > 0 {
+                    content_context.insert("series_prev", &series_posts[position - 1]);
+                }
+                if let Some(next) = series_posts.get(position + 1) {
+                    content_context.insert("series_next", next);
+                }
+            }
+        }
         debug!(
             "{} context: {:?}",
             &content.slug,
@@ -535,52 +1357,85 @@ fn handle_content_pages(
             )
         );
         render_html(
-            "content.html",
+            content_template(tera, content),
             &format!("{}.html", &content.slug),
             tera,
             &content_context,
             output_dir,
+            site_data,
         )?;
     }
     Ok(())
 }
 
+/// The `template` of the frontmatter, `content.html` when unset or missing
+fn content_template<'a>(tera: &Tera, content: &'a Content) -> &'a str {
+    let Some(template) = content.template.as_deref() else {
+        return "content.html";
+    };
+    if tera.get_template_names().any(|name| name == template) {
+        template
+    } else {
+        warn!(
+            "Template '{}' of {} not found, using content.html",
+            template,
+            content.source_path.display()
+        );
+        "content.html"
+    }
+}
+
+/// The older and the newer post around `posts[index]`. Posts are sorted
+/// newest first, so the previous (older) post comes after.
+fn adjacent_posts(posts: &[Content], index: usize) -> (Option<&Content>, Option<&Content>) {
+    let prev = posts.get(index + 1);
+    let next = index.checked_sub(1).and_then(|i| posts.get(i));
+    (prev, next)
+}
+
 #[allow(clippy::similar_names)]
 fn handle_404(
     content_dir: &Path,
+    site_data: &Data,
     global_context: &Context,
     tera: &Tera,
     output_dir: &Path,
 ) -> Result<(), String> {
-    let input_404_path = content_dir.join("_404.md");
+    let input_404_path = ["_404.md", "404.md"]
+        .iter()
+        .map(|name| content_dir.join(name))
+        .find(|path| path.exists());
     let mut context = global_context.clone();
     let mut content = Content {
-        html: String::from("Page not found :/"),
+        html: String::from("<h1>Page not found :/</h1>"),
         title: String::from("Page not found"),
-        description: None,
-        date: None,
         slug: "404".to_string(),
-        extra: None,
-        tags: vec![],
-        links_to: None,
-        back_links: vec![],
+        ..Default::default()
     };
-    if input_404_path.exists() {
-        let custom_content = get_content(&input_404_path)?;
+    if let Some(input_404_path) = input_404_path {
+        let custom_content =
+            get_content(&input_404_path, &site_data.site).map_err(|e| e.to_string())?;
         content.html.clone_from(&custom_content.html);
         content.title.clone_from(&custom_content.title);
     }
     context.insert("title", &content.title);
     context.insert("content", &content);
-    render_html("content.html", "404.html", tera, &context, output_dir)?;
+    render_html(
+        template_or(tera, "404.html", "content.html"),
+        "404.html",
+        tera,
+        &context,
+        output_dir,
+        site_data,
+    )?;
     Ok(())
 }
 
-fn get_html_fragment(filename: &str, content_dir: &Path) -> String {
+fn get_html_fragment(filename: &str, content_dir: &Path, site: &Marmite) -> String {
     let filepath = content_dir.join(filename);
     let mut fragment = String::new();
     if filepath.exists() {
-        match get_content(&filepath) {
+        match get_content(&filepath, site) {
             Ok(content) => fragment.push_str(&content.html),
             Err(e) => {
                 error!("Error parsing {}: {}", filepath.display(), e);
@@ -590,33 +1445,41 @@ fn get_html_fragment(filename: &str, content_dir: &Path) -> String {
     fragment
 }
 
+/// Tags and their post count, most used first
+fn unique_tags(site_data: &Data) -> Vec<(String, usize)> {
+    let mut unique_tags = site_data
+        .tags
+        .iter()
+        .map(|(tag, contents)| (tag.clone(), contents.len()))
+        .collect::<Vec<_>>();
+    unique_tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    unique_tags
+}
+
 fn handle_tag_pages(
     output_dir: &Path,
     site_data: &Data,
     global_context: &Context,
     tera: &Tera,
 ) -> Result<(), String> {
-    let mut unique_tags: Vec<(String, usize)> = Vec::new();
-    for (tag, tagged_contents) in group_by_tags(site_data.posts.clone()) {
-        // aggregate unique tags to render the tags list later
-        unique_tags.push((tag.clone(), tagged_contents.len()));
-        let tag_slug = slugify(&tag);
+    let template = template_or(tera, "tag.html", "list.html");
+    for (tag, tagged_contents) in &site_data.tags {
         handle_list_page(
             global_context,
-            &site_data.site.tags_content_title.replace("$tag", &tag),
-            &tagged_contents,
+            &site_data.site.tags_content_title.replace("$tag", tag),
+            tagged_contents,
             site_data,
             tera,
             output_dir,
-            format!("tag-{}", &tag_slug).as_ref(),
+            &group_page("tag", tag),
+            template,
         )?;
     }
 
     // Render tags.html group page
     let mut tag_list_context = global_context.clone();
     tag_list_context.insert("title", &site_data.site.tags_title);
-    unique_tags.sort_by(|a, b| b.1.cmp(&a.1));
-    tag_list_context.insert("group_content", &unique_tags);
+    tag_list_context.insert("group_content", &unique_tags(site_data));
     tag_list_context.insert("current_page", "tags.html");
     tag_list_context.insert("link_prefix", "tag");
     render_html(
@@ -625,6 +1488,7 @@ fn handle_tag_pages(
         tera,
         &tag_list_context,
         output_dir,
+        site_data,
     )?;
     Ok(())
 }
@@ -632,35 +1496,49 @@ fn handle_tag_pages(
 fn handle_archive_pages(
     output_dir: &Path,
     site_data: &Data,
+    archive: &[(ArchiveYear, Vec<Vec<Content>>)],
     global_context: &Context,
     tera: &Tera,
 ) -> Result<(), String> {
+    let template = template_or(tera, "archive.html", "list.html");
     let mut unique_years: Vec<(String, usize)> = Vec::new();
-    let mut grouped_posts: HashMap<String, Vec<Content>> = HashMap::new();
-    let posts = site_data.posts.clone();
-    for post in posts {
-        if let Some(date) = post.date {
-            let year = date.year().to_string();
-            grouped_posts.entry(year).or_default().push(post);
-        }
-    }
 
-    // render each year page
-    for (year, contents) in &grouped_posts {
+    // render each year page, then one page for each of its months
+    for (archive_year, months) in archive {
+        let year = archive_year.year.to_string();
+        let year_contents = months.concat();
         handle_list_page(
             global_context,
-            &site_data.site.archives_content_title.replace("$year", year),
-            contents,
+            &site_data
+                .site
+                .archives_content_title
+                .replace("$year", &year),
+            &year_contents,
             site_data,
             tera,
             output_dir,
-            format!("archive-{year}").as_ref(),
+            &archive_page(archive_year.year, None),
+            template,
         )?;
-        unique_years.push((year.to_owned(), contents.len()));
+        for (archive_month, contents) in archive_year.months.iter().zip(months) {
+            handle_list_page(
+                global_context,
+                &site_data
+                    .site
+                    .archives_content_title
+                    .replace("$year", &format!("{} {year}", archive_month.name)),
+                contents,
+                site_data,
+                tera,
+                output_dir,
+                &archive_page(archive_year.year, Some(archive_month.number)),
+                template,
+            )?;
+        }
+        unique_years.push((year, archive_year.count));
     }
 
     // Render archive.html group page
-    unique_years.sort_by(|a, b| b.cmp(a));
     let mut archive_context = global_context.clone();
     archive_context.insert("title", &site_data.site.archives_title);
     archive_context.insert("group_content", &unique_years);
@@ -672,17 +1550,148 @@ fn handle_archive_pages(
         tera,
         &archive_context,
         output_dir,
+        site_data,
     )?;
 
     Ok(())
 }
 
+fn handle_category_pages(
+    output_dir: &Path,
+    site_data: &Data,
+    global_context: &Context,
+    tera: &Tera,
+) -> Result<(), String> {
+    let template = template_or(tera, "category.html", "list.html");
+    let mut unique_categories: Vec<(String, usize)> = Vec::new();
+    for (category, contents) in &site_data.categories {
+        handle_list_page(
+            global_context,
+            &site_data
+                .site
+                .categories_content_title
+                .replace("$category", category),
+            contents,
+            site_data,
+            tera,
+            output_dir,
+            &group_page("category", category),
+            template,
+        )?;
+        unique_categories.push((category.to_owned(), contents.len()));
+    }
+
+    // Render categories.html group page
+    unique_categories.sort();
+    let mut categories_context = global_context.clone();
+    categories_context.insert("title", &site_data.site.categories_title);
+    categories_context.insert("group_content", &unique_categories);
+    categories_context.insert("current_page", "categories.html");
+    categories_context.insert("link_prefix", "category");
+    render_html(
+        "group.html",
+        "categories.html",
+        tera,
+        &categories_context,
+        output_dir,
+        site_data,
+    )?;
+
+    Ok(())
+}
+
+fn handle_series_pages(
+    output_dir: &Path,
+    site_data: &Data,
+    global_context: &Context,
+    tera: &Tera,
+) -> Result<(), String> {
+    let template = template_or(tera, "series.html", "list.html");
+    for (series, contents) in &site_data.series {
+        handle_list_page(
+            global_context,
+            &site_data
+                .site
+                .series_content_title
+                .replace("$series", series),
+            contents,
+            site_data,
+            tera,
+            output_dir,
+            &group_page("series", series),
+            template,
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns `template` if it exists in the loaded templates, else `fallback`
+fn template_or<'a>(tera: &Tera, template: &'a str, fallback: &'a str) -> &'a str {
+    if tera.get_template_names().any(|name| name == template) {
+        template
+    } else {
+        fallback
+    }
+}
+
+/// Minifies the rendered HTML, leaving inline JS and CSS untouched
+fn minify(rendered: String, filename: &str) -> Vec<u8> {
+    let cfg = minify_html::Cfg {
+        keep_closing_tags: true,
+        keep_html_and_head_opening_tags: true,
+        minify_css: false,
+        minify_js: false,
+        ..minify_html::Cfg::default()
+    };
+    let minified = minify_html::minify(rendered.as_bytes(), &cfg);
+    if minified.is_empty() && !rendered.trim().is_empty() {
+        warn!("Unable to minify {}, writing it as rendered", filename);
+        return rendered.into_bytes();
+    }
+    minified
+}
+
+/// Points the `./` links of a page `depth` subfolders deep (a `permalink`
+/// with `/`) that many levels up, and its `static/`, `media/` and feed links
+/// too. In a language subdirectory `static/`, `media/` and `<lang>/` go one
+/// more level up, where those folders are.
+fn relocate_links(mut rendered: String, site_data: &Data, depth: usize) -> String {
+    let site = &site_data.site;
+    let up = "../".repeat(depth);
+    if depth > 0 {
+        rendered = rendered.replace("=\"./", &format!("=\"{up}"));
+        if !site.feed_url.contains("://") {
+            rendered = rendered.replace(
+                &format!("=\"{}\"", site.feed_url),
+                &format!("=\"{up}{}\"", site.feed_url),
+            );
+        }
+    }
+    let (root, languages) = match site_data.lang {
+        Some(_) => (format!("../{up}"), site_data.languages.as_slice()),
+        None => (up.clone(), [].as_slice()),
+    };
+    let folders = [&site.static_path, &site.media_path]
+        .into_iter()
+        .chain(languages);
+    for folder in folders {
+        for prefix in ["./", &up, ""] {
+            rendered = rendered.replace(
+                &format!("=\"{prefix}{folder}/"),
+                &format!("=\"{root}{folder}/"),
+            );
+        }
+    }
+    rendered
+}
+
 fn render_html(
     template: &str,
     filename: &str,
     tera: &Tera,
     context: &Context,
     output_dir: &Path,
+    site_data: &Data,
 ) -> Result<(), String> {
     let rendered = tera.render(template, context).map_err(|e| {
         debug!(
@@ -691,8 +1700,182 @@ fn render_html(
         );
         e.to_string()
     })?;
+    if site_data.check {
+        debug!("Rendered {} (check mode, not written)", filename);
+        return Ok(());
+    }
+    let depth = filename.matches('/').count();
+    let rendered = if site_data.lang.is_some() || depth > 0 {
+        relocate_links(rendered, site_data, depth)
+    } else {
+        rendered
+    };
+    let rendered = if site_data.site.minify_html {
+        minify(rendered, filename)
+    } else {
+        rendered.into_bytes()
+    };
     let output_file = output_dir.join(filename);
+    if let Some(parent) = output_file.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
     fs::write(&output_file, rendered).map_err(|e| e.to_string())?;
     info!("Generated {}", &output_file.display());
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn post(slug: &str) -> Content {
+        Content {
+            slug: slug.to_string(),
+            ..Content::default()
+        }
+    }
+
+    fn slugs<'a>(
+        adjacent: (Option<&'a Content>, Option<&'a Content>),
+    ) -> (Option<&'a str>, Option<&'a str>) {
+        (
+            adjacent.0.map(|c| c.slug.as_str()),
+            adjacent.1.map(|c| c.slug.as_str()),
+        )
+    }
+
+    #[test]
+    fn newest_post_has_no_next() {
+        let posts = [post("newest"), post("middle"), post("oldest")];
+        assert_eq!(slugs(adjacent_posts(&posts, 0)), (Some("middle"), None));
+    }
+
+    #[test]
+    fn oldest_post_has_no_prev() {
+        let posts = [post("newest"), post("middle"), post("oldest")];
+        assert_eq!(slugs(adjacent_posts(&posts, 2)), (None, Some("middle")));
+    }
+
+    #[test]
+    fn middle_post_has_both() {
+        let posts = [post("newest"), post("middle"), post("oldest")];
+        assert_eq!(
+            slugs(adjacent_posts(&posts, 1)),
+            (Some("oldest"), Some("newest"))
+        );
+    }
+
+    #[test]
+    fn single_post_has_neither() {
+        let posts = [post("only")];
+        assert_eq!(slugs(adjacent_posts(&posts, 0)), (None, None));
+    }
+
+    fn dated_post(slug: &str, date: &str) -> Content {
+        Content {
+            date: chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").ok(),
+            source_path: std::path::PathBuf::from(format!("content/{slug}.md")),
+            ..post(slug)
+        }
+    }
+
+    #[test]
+    fn permalink_expands_the_date_tokens() {
+        let mut site_data = Data::new(
+            "permalink: \"{year}-{month}-{day}-{slug}\"",
+            Path::new("marmite.yaml"),
+        );
+        let cli_args = Cli::parse_from(["marmite", "in", "out"]);
+        process_file(
+            dated_post("hello", "2024-06-03 10:00"),
+            &mut site_data,
+            &cli_args,
+        );
+        assert_eq!(site_data.posts[0].slug, "2024-06-03-hello");
+    }
+
+    #[test]
+    fn colliding_permalinks_are_detected() {
+        let mut site_data = Data::new("permalink: \"{year}-{month}\"", Path::new("marmite.yaml"));
+        let cli_args = Cli::parse_from(["marmite", "in", "out"]);
+        process_file(
+            dated_post("first", "2024-06-03 10:00"),
+            &mut site_data,
+            &cli_args,
+        );
+        process_file(
+            dated_post("second", "2024-06-20 10:00"),
+            &mut site_data,
+            &cli_args,
+        );
+        let errors = detect_slug_collision(&site_data, &cli_args);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("'2024-06'"));
+    }
+
+    #[test]
+    fn nested_permalinks_write_subfolders() {
+        let mut site_data = Data::new(
+            "permalink: \"/{year}/{month}/{slug}/\"",
+            Path::new("marmite.yaml"),
+        );
+        let cli_args = Cli::parse_from(["marmite", "in", "out"]);
+        process_file(
+            dated_post("hello", "2024-06-03 10:00"),
+            &mut site_data,
+            &cli_args,
+        );
+        assert_eq!(site_data.posts[0].slug, "2024/06/hello");
+    }
+
+    #[test]
+    fn nested_pages_link_up_to_the_root() {
+        let mut site_data = Data::new("", Path::new("marmite.yaml"));
+        site_data.site.feed_url = "feed.xml".to_string();
+        let rendered = r##"<a href="./about.html"><img src="media/a.png"><link href="feed.xml"><a href="#top">"##;
+        assert_eq!(
+            relocate_links(rendered.to_string(), &site_data, 2),
+            r##"<a href="../../about.html"><img src="../../media/a.png"><link href="../../feed.xml"><a href="#top">"##
+        );
+        site_data.lang = Some("pt".to_string());
+        site_data.languages = vec!["en".to_string(), "pt".to_string()];
+        let rendered =
+            r##"<a href="./about.html"><link href="./static/a.css"><a href="./en/index.html">"##;
+        assert_eq!(
+            relocate_links(rendered.to_string(), &site_data, 1),
+            r##"<a href="../about.html"><link href="../../static/a.css"><a href="../../en/index.html">"##
+        );
+    }
+
+    #[test]
+    fn group_lists_are_written_in_folders() {
+        let mut site_data = Data::new("pagination: 1", Path::new("marmite.yaml"));
+        let cli_args = Cli::parse_from(["marmite", "in", "out"]);
+        for (slug, date) in [
+            ("first", "2024-06-03 10:00"),
+            ("second", "2024-07-01 10:00"),
+        ] {
+            let mut post = dated_post(slug, date);
+            post.tags = vec!["Café".to_string()];
+            process_file(post, &mut site_data, &cli_args);
+        }
+        organize_content(&mut site_data);
+        let pages = rendered_pages(&site_data)
+            .into_iter()
+            .map(|(page, _)| page)
+            .collect::<Vec<_>>();
+        for page in [
+            "tag/cafe/index.html",
+            "tag/cafe/index-2.html",
+            "archive/2024/index.html",
+            "archive/2024/06/index.html",
+        ] {
+            assert!(pages.iter().any(|p| p == page), "{page} in {pages:?}");
+        }
+
+        let pagination = Pagination::new("tag/cafe/index", 2, 2, 1);
+        assert_eq!(pagination.prev_url.as_deref(), Some("index.html"));
+        assert_eq!(pagination.last_url, "index-2.html");
+    }
+}
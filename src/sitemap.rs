@@ -0,0 +1,62 @@
+use crate::feed::{escape_xml, site_url};
+use crate::site::{rendered_pages, Data};
+use chrono::{SecondsFormat, Utc};
+use log::{error, info, warn};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Writes `robots.txt` allowing everything and pointing to the sitemap,
+/// followed by any `robots_txt_extra` directives.
+pub fn generate_robots_txt(site_data: &Data, output_dir: &Path) {
+    let mut robots = String::from("User-agent: *\nAllow: /\n");
+    // the Sitemap directive requires an absolute URL
+    if site_data.site.sitemap && !site_data.site.url.is_empty() {
+        let _ = writeln!(robots, "Sitemap: {}", site_data.site.sitemap_url);
+    }
+    if let Some(extra) = &site_data.site.robots_txt_extra {
+        let _ = writeln!(robots, "\n{}", extra.trim_end());
+    }
+    let robots_path = output_dir.join("robots.txt");
+    if let Err(e) = fs::write(&robots_path, robots) {
+        error!("Failed to write robots.txt: {}", e);
+    } else {
+        info!("Generated {}", robots_path.display());
+    }
+}
+
+/// Writes `sitemap.xml` listing every generated HTML page (except 404.html
+/// and `redirect_from` pages), using the content date as `lastmod` when
+/// available, else the build date. Skipped without `url`, the sitemap
+/// requires absolute URLs.
+pub fn generate_sitemap(site_data: &Data, output_dir: &Path) {
+    if site_data.site.url.is_empty() {
+        warn!("Skipping sitemap.xml, it requires absolute URLs: set `url` in the config");
+        return;
+    }
+    let build_date = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut pages = rendered_pages(site_data);
+    pages.sort();
+
+    let mut urls = String::new();
+    for (page, date) in &pages {
+        let lastmod = date.map_or_else(|| build_date.clone(), |date| date.and_utc().to_rfc3339());
+        let _ = writeln!(
+            urls,
+            "<url><loc>{}</loc><lastmod>{}</lastmod><changefreq>{}</changefreq></url>",
+            escape_xml(&site_url(site_data, page)),
+            lastmod,
+            site_data.site.sitemap_changefreq
+        );
+    }
+    let sitemap = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n{urls}</urlset>\n"
+    );
+    let sitemap_path = output_dir.join("sitemap.xml");
+    if let Err(e) = fs::write(&sitemap_path, sitemap) {
+        error!("Failed to write sitemap.xml: {}", e);
+    } else {
+        info!("Generated {}", sitemap_path.display());
+    }
+}
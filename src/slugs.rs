@@ -0,0 +1,286 @@
+use crate::cache;
+use crate::cli::Cli;
+use crate::redirects::redirect_file_name;
+use crate::site::{self, Data};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const REGISTRY_FILE: &str = "slugs.json";
+
+/// A built content, as recorded in `.marmite/slugs.json`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SlugEntry {
+    pub slug: String,
+    pub title: String,
+    pub source: PathBuf,
+    pub redirect_from: Vec<String>,
+    /// Every slug the content had in earlier builds
+    #[serde(default)]
+    pub old_slugs: Vec<String>,
+}
+
+impl SlugEntry {
+    /// Old slugs not redirected yet by `redirect_from`, drafts get a
+    /// `draft-` slug until published, that is not a URL to keep
+    fn pending_redirects(&self) -> impl Iterator<Item = &String> {
+        self.old_slugs.iter().filter(|old| {
+            let old_file = format!("{old}.html");
+            **old != self.slug
+                && !old.starts_with("draft-")
+                && !self
+                    .redirect_from
+                    .iter()
+                    .any(|path| redirect_file_name(path) == old_file)
+        })
+    }
+}
+
+/// Writes the slugs of the built posts and pages, so `--migrate-slugs` can
+/// tell which of them changed. The old slugs of a content are kept from
+/// build to build, matched by source file or else by title for a renamed
+/// file, so the builds between a rename and `--migrate-slugs` don't lose
+/// them.
+pub fn write_registry(site_data: &Data, state_dir: &Path) {
+    let previous = read_registry(state_dir).unwrap_or_default();
+    let contents = site_data
+        .posts
+        .iter()
+        .chain(&site_data.pages)
+        .collect::<Vec<_>>();
+    let sources = contents
+        .iter()
+        .map(|content| &content.source_path)
+        .collect::<HashSet<_>>();
+    let previous_sources = previous
+        .iter()
+        .map(|entry| &entry.source)
+        .collect::<HashSet<_>>();
+    let entries = contents
+        .iter()
+        .map(|content| {
+            let history = previous
+                .iter()
+                .find(|old| old.source == content.source_path)
+                .or_else(|| {
+                    if previous_sources.contains(&content.source_path) {
+                        return None;
+                    }
+                    previous
+                        .iter()
+                        .find(|old| !sources.contains(&old.source) && old.title == content.title)
+                });
+            let mut old_slugs = history.map(|old| old.old_slugs.clone()).unwrap_or_default();
+            if let Some(old) = history {
+                if !old_slugs.contains(&old.slug) {
+                    old_slugs.push(old.slug.clone());
+                }
+            }
+            old_slugs.retain(|slug| *slug != content.slug);
+            SlugEntry {
+                slug: content.slug.clone(),
+                title: content.title.clone(),
+                source: content.source_path.clone(),
+                redirect_from: content.redirect_from.clone(),
+                old_slugs,
+            }
+        })
+        .collect::<Vec<_>>();
+    let registry_path = state_dir.join(REGISTRY_FILE);
+    let result = serde_json::to_string_pretty(&entries)
+        .map_err(|e| e.to_string())
+        .and_then(|raw| {
+            fs::create_dir_all(state_dir)
+                .and_then(|()| fs::write(&registry_path, raw))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        debug!("Unable to write {}: {}", registry_path.display(), e);
+    }
+}
+
+fn read_registry(state_dir: &Path) -> Option<Vec<SlugEntry>> {
+    let raw = fs::read_to_string(state_dir.join(REGISTRY_FILE)).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+/// Builds the site and prints a `redirect_from` entry for each old slug of
+/// a content that is not redirected yet (`--migrate-slugs`). With `--write`
+/// the entries are also added to the YAML frontmatter of the content files,
+/// until then every run proposes them again.
+pub fn migrate_slugs(
+    config_path: &Path,
+    input_folder: &Path,
+    output_folder: &Arc<PathBuf>,
+    cli_args: &Arc<Cli>,
+) {
+    let state_dir = cache::state_dir(input_folder);
+    let Some(previous) = read_registry(&state_dir) else {
+        error!(
+            "No {} in {}, build the site once before renaming contents",
+            REGISTRY_FILE,
+            state_dir.display()
+        );
+        return;
+    };
+
+    site::generate(
+        config_path,
+        input_folder,
+        output_folder,
+        false,
+        false,
+        &cli_args.bind,
+        cli_args,
+    );
+    let current = read_registry(&state_dir).unwrap_or_default();
+    let known_slugs = current
+        .iter()
+        .flat_map(|entry| std::iter::once(&entry.slug).chain(&entry.old_slugs))
+        .collect::<HashSet<_>>();
+    for old in previous
+        .iter()
+        .filter(|old| !known_slugs.contains(&old.slug) && !old.slug.starts_with("draft-"))
+    {
+        warn!(
+            "'{}' ({}) is gone and no content matches it",
+            old.slug,
+            old.source.display()
+        );
+    }
+
+    let mut proposals = 0;
+    for new in &current {
+        for old_slug in new.pending_redirects() {
+            let redirect = format!("/{old_slug}");
+            println!(
+                "# {} ({} -> {})\nredirect_from:\n  - {}\n",
+                new.source.display(),
+                old_slug,
+                new.slug,
+                redirect
+            );
+            proposals += 1;
+            if cli_args.write {
+                match add_redirect_from(&new.source, &redirect) {
+                    Ok(()) => info!("Added {} to {}", redirect, new.source.display()),
+                    Err(e) => error!("Unable to update {}: {}", new.source.display(), e),
+                }
+            }
+        }
+    }
+    if proposals == 0 {
+        info!("No old slug left to redirect");
+    } else if !cli_args.write {
+        info!("Add the entries above to the frontmatter, or run again with --write");
+    }
+}
+
+/// Adds `path` to the `redirect_from` list of a file with YAML frontmatter
+fn add_redirect_from(source: &Path, path: &str) -> Result<(), String> {
+    let text = fs::read_to_string(source).map_err(|e| e.to_string())?;
+    let entry = format!("  - {path}\n");
+    let updated = if let Some(frontmatter) = text.strip_prefix("---\n") {
+        let end = if frontmatter.starts_with("---") {
+            0
+        } else {
+            frontmatter
+                .find("\n---")
+                .ok_or("the frontmatter is not closed")?
+                + 1
+        };
+        let (head, tail) = frontmatter.split_at(end);
+        let mut lines = head
+            .lines()
+            .map(|line| format!("{line}\n"))
+            .collect::<Vec<_>>();
+        match lines
+            .iter()
+            .position(|line| line.starts_with("redirect_from:"))
+        {
+            Some(index) if lines[index].trim_end() == "redirect_from:" => {
+                lines.insert(index + 1, entry);
+            }
+            Some(_) => return Err("`redirect_from` is not a YAML list, edit it by hand".into()),
+            None => {
+                lines.push("redirect_from:\n".to_string());
+                lines.push(entry);
+            }
+        }
+        format!("---\n{}{tail}", lines.concat())
+    } else if text.starts_with("---") || text.starts_with("+++") || text.starts_with('{') {
+        return Err("only YAML frontmatter can be updated, edit it by hand".into());
+    } else {
+        format!("---\nredirect_from:\n{entry}---\n{text}")
+    };
+    fs::write(source, updated).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn build(input: &Path, output: &Arc<PathBuf>, args: &[&str]) -> Arc<Cli> {
+        let cli_args = Arc::new(Cli::parse_from(
+            ["marmite", input.to_str().unwrap(), output.to_str().unwrap()]
+                .into_iter()
+                .chain(args.iter().copied()),
+        ));
+        let config_path = input.join(&cli_args.config);
+        if cli_args.migrate_slugs {
+            migrate_slugs(&config_path, input, output, &cli_args);
+        } else {
+            site::generate(
+                &config_path,
+                input,
+                output,
+                false,
+                false,
+                &cli_args.bind,
+                &cli_args,
+            );
+        }
+        cli_args
+    }
+
+    fn pending(input: &Path) -> Vec<String> {
+        read_registry(&cache::state_dir(input))
+            .unwrap()
+            .iter()
+            .flat_map(|entry| entry.pending_redirects().cloned().collect::<Vec<_>>())
+            .collect()
+    }
+
+    #[test]
+    fn dry_run_keeps_the_old_slug_for_write() {
+        let dir = std::env::temp_dir().join(format!("marmite-slugs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let input = dir.join("site");
+        let output = Arc::new(dir.join("public"));
+        let post = input.join("content/2024-01-01-post.md");
+        fs::create_dir_all(post.parent().unwrap()).unwrap();
+        fs::write(&post, "---\nslug: hello\n---\n# Hello\n").unwrap();
+        build(&input, &output, &[]);
+
+        fs::write(&post, "---\nslug: hello-world\n---\n# Hello\n").unwrap();
+        // a normal build and a dry run in between don't lose the old slug
+        build(&input, &output, &[]);
+        build(&input, &output, &["--migrate-slugs"]);
+        assert!(!fs::read_to_string(&post).unwrap().contains("redirect_from"));
+        assert_eq!(pending(&input), ["hello"]);
+
+        build(&input, &output, &["--migrate-slugs", "--write"]);
+        assert_eq!(
+            fs::read_to_string(&post).unwrap(),
+            "---\nslug: hello-world\nredirect_from:\n  - /hello\n---\n# Hello\n"
+        );
+        build(&input, &output, &[]);
+        assert!(pending(&input).is_empty());
+        assert!(output.join("hello.html").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use crate::cli::Cli;
+use crate::content::Content;
+use crate::site::load_site_data;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// Width of the longest bar of the histograms
+const BAR_WIDTH: usize = 40;
+
+#[derive(Serialize)]
+struct PostLength {
+    title: String,
+    slug: String,
+    words: usize,
+}
+
+impl PostLength {
+    fn new(post: &Content) -> Self {
+        Self {
+            title: post.title.clone(),
+            slug: post.slug.clone(),
+            words: post.word_count,
+        }
+    }
+}
+
+/// Parses every content, drafts included, without writing the site and
+/// prints the counts of posts, pages and drafts, the average words per post,
+/// the 20 most used tags, the posts per month and the 5 longest and shortest
+/// posts (`--stats`), as tables or as JSON with `--format json`.
+pub fn print_stats(config_path: &Path, input_folder: &Path, cli_args: &Cli) {
+    let mut cli_args = cli_args.clone();
+    cli_args.include_drafts = true;
+    let (site_data, _) = load_site_data(config_path, input_folder, &cli_args);
+
+    let (drafts, posts): (Vec<_>, Vec<_>) = site_data.posts.iter().partition(|post| post.draft);
+    let (page_drafts, pages): (Vec<_>, Vec<_>) =
+        site_data.pages.iter().partition(|page| page.draft);
+    let draft_count = drafts.len() + page_drafts.len();
+    let post_words = posts.iter().map(|post| post.word_count).sum::<usize>();
+    let page_words = pages.iter().map(|page| page.word_count).sum::<usize>();
+    #[allow(clippy::cast_precision_loss)]
+    let average_words = if posts.is_empty() {
+        0.0
+    } else {
+        post_words as f64 / posts.len() as f64
+    };
+
+    let mut tag_counts: HashMap<&str, usize> = HashMap::new();
+    for tag in posts.iter().flat_map(|post| &post.tags) {
+        *tag_counts.entry(tag.as_str()).or_default() += 1;
+    }
+    let mut tags = tag_counts.into_iter().collect::<Vec<_>>();
+    tags.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    tags.truncate(20);
+
+    let mut months: BTreeMap<String, usize> = BTreeMap::new();
+    for date in posts.iter().filter_map(|post| post.date) {
+        *months.entry(date.format("%Y-%m").to_string()).or_default() += 1;
+    }
+
+    let mut by_length = posts.clone();
+    by_length.sort_by(|a, b| b.word_count.cmp(&a.word_count).then(a.title.cmp(&b.title)));
+    let longest = by_length
+        .iter()
+        .take(5)
+        .map(|post| PostLength::new(post))
+        .collect::<Vec<_>>();
+    let shortest = by_length
+        .iter()
+        .rev()
+        .take(5)
+        .map(|post| PostLength::new(post))
+        .collect::<Vec<_>>();
+
+    if cli_args.format == "json" {
+        let tags = tags
+            .iter()
+            .map(|(tag, count)| json!({"tag": tag, "count": count}))
+            .collect::<Vec<_>>();
+        let months = months
+            .iter()
+            .map(|(month, count)| json!({"month": month, "count": count}))
+            .collect::<Vec<_>>();
+        let report = json!({
+            "posts": posts.len(),
+            "pages": pages.len(),
+            "drafts": draft_count,
+            "post_words": post_words,
+            "page_words": page_words,
+            "average_words_per_post": average_words,
+            "tags": tags,
+            "posts_per_month": months,
+            "longest_posts": longest,
+            "shortest_posts": shortest,
+        });
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).unwrap_or_default()
+        );
+        return;
+    }
+
+    println!("{:<8}{:>8}{:>10}", "Content", "Count", "Words");
+    println!("{:<8}{:>8}{:>10}", "posts", posts.len(), post_words);
+    println!("{:<8}{:>8}{:>10}", "pages", pages.len(), page_words);
+    println!("{:<8}{:>8}", "drafts", draft_count);
+    println!(
+        "{:<8}{:>8}{:>10}",
+        "total",
+        posts.len() + pages.len(),
+        post_words + page_words
+    );
+    println!("\nAverage words per post: {average_words:.0}");
+
+    print_histogram(
+        "Top tags",
+        &tags
+            .iter()
+            .map(|(tag, count)| ((*tag).to_string(), *count))
+            .collect::<Vec<_>>(),
+    );
+    print_histogram("Posts per month", &months.into_iter().collect::<Vec<_>>());
+
+    for (title, lengths) in [("Longest posts", &longest), ("Shortest posts", &shortest)] {
+        println!("\n{title}");
+        for length in lengths {
+            println!("{:>8}  {}", length.words, length.title);
+        }
+    }
+}
+
+/// Prints each label with its count and a bar as long as the count, relative
+/// to the highest one
+fn print_histogram(title: &str, rows: &[(String, usize)]) {
+    println!("\n{title}");
+    let Some(max) = rows.iter().map(|(_, count)| *count).max() else {
+        println!("  (none)");
+        return;
+    };
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    for (label, count) in rows {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max));
+        println!("  {label:<width$} {count:>5} {bar}");
+    }
+}
@@ -1,4 +1,6 @@
 #![allow(clippy::module_name_repetitions)]
+use crate::cli::Cli;
+use crate::config::{parse_config, read_config};
 use crate::embedded::{generate_static, Templates};
 use log::{error, info};
 use std::fs;
@@ -43,3 +45,127 @@ pub fn initialize_theme_assets(input_folder: &Path) {
     }
     generate_static(&static_path);
 }
+
+const INIT_CONFIG: &str = include_str!("init/marmite.yaml");
+const INIT_POST: &str = include_str!("init/hello-world.md");
+const INIT_GITIGNORE: &str = include_str!("init/gitignore");
+const INIT_TEMPLATES: [(&str, &str); 2] = [
+    ("list.html", include_str!("../example/templates/list.html")),
+    (
+        "content.html",
+        include_str!("../example/templates/content.html"),
+    ),
+];
+
+/// Scaffolds a new project in `folder` with a config, a sample post, the
+/// `list.html` and `content.html` templates to start customizing and a
+/// `.gitignore` leaving out the `.marmite/` folder the builds write.
+pub fn initialize_project(folder: &Path) {
+    if folder.exists() {
+        error!("Folder {} already exists, aborting", folder.display());
+        return;
+    }
+
+    let files = [
+        (folder.join("marmite.yaml"), INIT_CONFIG),
+        (folder.join("content").join("hello-world.md"), INIT_POST),
+        (folder.join(".gitignore"), INIT_GITIGNORE),
+    ]
+    .into_iter()
+    .chain(
+        INIT_TEMPLATES
+            .iter()
+            .map(|(name, template)| (folder.join("templates").join(name), *template)),
+    );
+    for (path, text) in files {
+        if let Some(parent) = path.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                error!("Failed to create {}: {}", parent.display(), e);
+                return;
+            }
+        }
+        if let Err(e) = fs::write(&path, text) {
+            error!("Failed to write {}: {}", path.display(), e);
+            return;
+        }
+        info!("Generated {}", path.display());
+    }
+
+    info!(
+        "Next steps: edit {}/content/hello-world.md and run `marmite {} site --serve --watch`",
+        folder.display(),
+        folder.display()
+    );
+}
+
+const DEPLOY_WORKFLOW: &str = include_str!("init/deploy.yml");
+
+/// Writes `.github/workflows/deploy.yml` at the root of the git repository
+/// holding `input_folder`, a workflow building the site on every push to
+/// `main` and publishing `output_folder` (`site` if not given) plus the
+/// configured `site_path` to the `gh-pages` branch.
+pub fn initialize_github_pages(
+    input_folder: &Path,
+    output_folder: Option<&Path>,
+    config_path: &Path,
+    cli_args: &Cli,
+) {
+    let config_str = match read_config(config_path, cli_args) {
+        Ok(config_str) => config_str,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let site = match parse_config(&config_str, config_path) {
+        Ok(site) => site,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let input = input_folder
+        .canonicalize()
+        .unwrap_or_else(|_| input_folder.to_path_buf());
+    // the workflow must be at the repository root, which can be above the input
+    let repo_root = input
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(&input)
+        .to_path_buf();
+    let relative_input = match input.strip_prefix(&repo_root) {
+        Ok(path) if !path.as_os_str().is_empty() => path.display().to_string(),
+        _ => ".".to_string(),
+    };
+    let output = output_folder.unwrap_or(Path::new("site"));
+    let folder = output.join(&site.site_path);
+
+    let workflow_path = repo_root
+        .join(".github")
+        .join("workflows")
+        .join("deploy.yml");
+    if workflow_path.exists() {
+        error!("{} already exists, aborting", workflow_path.display());
+        return;
+    }
+    let workflow = DEPLOY_WORKFLOW
+        .replace("{input}", &relative_input)
+        .replace("{output}", &output.display().to_string())
+        .replace("{folder}", &folder.display().to_string());
+    if let Some(parent) = workflow_path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            error!("Failed to create {}: {}", parent.display(), e);
+            return;
+        }
+    }
+    if let Err(e) = fs::write(&workflow_path, workflow) {
+        error!("Failed to write {}: {}", workflow_path.display(), e);
+        return;
+    }
+    info!("Generated {}", workflow_path.display());
+    info!(
+        "Next steps: push it to GitHub, then in the repository Settings > Pages \
+        choose `Deploy from a branch` and the `gh-pages` branch once the first run finishes"
+    );
+}
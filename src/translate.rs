@@ -0,0 +1,294 @@
+use crate::cli::Cli;
+use crate::site::load_site_data;
+use log::{error, info};
+use regex::Regex;
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+/// Marks the blocks left to translate, an HTML comment so it is not rendered
+const MARKER: &str = "<!-- TRANSLATE -->";
+
+/// DeepL accepts up to 50 texts per request
+const DEEPL_BATCH: usize = 50;
+
+/// A run of lines between blank lines, or a fenced code block. The blank
+/// lines of a list or a blockquote stay in its block.
+struct Block {
+    text: String,
+    code: bool,
+}
+
+/// Copies the post or page `slug` to a `<name>.<lang>.md` draft next to it,
+/// with `lang` in the frontmatter and a `<!-- TRANSLATE -->` marker above each
+/// block of text (`--translate`). With `--deepl-key` the blocks are
+/// pre-translated by DeepL, the frontmatter and code blocks are kept as is.
+pub fn translate(config_path: &Path, input_folder: &Path, slug: &str, lang: &str, cli_args: &Cli) {
+    // `about.fr.md` style names only take ISO 639-1 codes
+    if lang.len() != 2 || !lang.bytes().all(|b| b.is_ascii_lowercase()) {
+        error!(
+            "Invalid language '{}', use a two-letter lowercase code like 'fr'",
+            lang
+        );
+        process::exit(1);
+    }
+
+    let mut cli_args = cli_args.clone();
+    cli_args.include_drafts = true;
+    let (site_data, _) = load_site_data(config_path, input_folder, &cli_args);
+    let Some(source) = site_data
+        .posts
+        .iter()
+        .chain(&site_data.pages)
+        .find(|content| content.slug == slug)
+        .map(|content| content.source_path.clone())
+    else {
+        error!("No post or page with slug '{}'", slug);
+        process::exit(1);
+    };
+    if source.extension().is_none_or(|ext| ext != "md") {
+        error!(
+            "Only markdown files can be translated, {}",
+            source.display()
+        );
+        process::exit(1);
+    }
+
+    let target = translation_path(&source, lang);
+    if target.exists() {
+        error!("{} already exists, edit it instead", target.display());
+        process::exit(1);
+    }
+    let text = fs::read_to_string(&source).unwrap_or_else(|e| {
+        error!("Unable to read {}: {}", source.display(), e);
+        process::exit(1);
+    });
+    let (frontmatter, body) = split_frontmatter(&text, lang).unwrap_or_else(|e| {
+        error!("Unable to translate {}: {}", source.display(), e);
+        process::exit(1);
+    });
+
+    let mut blocks = split_blocks(body);
+    if let Some(key) = &cli_args.deepl_key {
+        if let Err(e) = deepl_translate(&mut blocks, key, lang) {
+            error!("Unable to translate with DeepL: {}", e);
+            process::exit(1);
+        }
+    }
+    let body = blocks
+        .iter()
+        .map(|block| {
+            if block.code {
+                block.text.clone()
+            } else {
+                format!("{MARKER}\n{}", block.text)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    if let Err(e) = fs::write(&target, format!("{frontmatter}\n{body}\n")) {
+        error!("Unable to write {}: {}", target.display(), e);
+        process::exit(1);
+    }
+    info!("Created {}", target.display());
+}
+
+/// `2024-01-01-my-post.md` gives `2024-01-01-my-post.fr.md`
+fn translation_path(source: &Path, lang: &str) -> PathBuf {
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    source.with_file_name(format!("{stem}.{lang}.md"))
+}
+
+/// The frontmatter with `lang` and `draft: true` set, and the markdown after
+/// it. Files without frontmatter get a YAML one.
+fn split_frontmatter<'a>(text: &'a str, lang: &str) -> Result<(String, &'a str), String> {
+    let (fence, separator) = if text.starts_with("---") {
+        ("---", ":")
+    } else if text.starts_with("+++") {
+        ("+++", "=")
+    } else if text.starts_with('{') {
+        return Err("JSON frontmatter is not supported, use YAML or TOML".to_string());
+    } else {
+        return Ok((format!("---\nlang: {lang}\ndraft: true\n---\n"), text));
+    };
+    let mut lines = text.split_inclusive('\n');
+    let mut offset = lines.next().map_or(0, str::len);
+    let mut fields = Vec::new();
+    let replaced = Regex::new(&format!(r"^(lang|draft)\s*{separator}")).unwrap();
+    for line in lines {
+        offset += line.len();
+        if line.trim_end() == fence {
+            if separator == ":" {
+                fields.push(format!("lang: {lang}"));
+                fields.push("draft: true".to_string());
+            } else {
+                fields.push(format!("lang = \"{lang}\""));
+                fields.push("draft = true".to_string());
+            }
+            let frontmatter = format!("{fence}\n{}\n{fence}\n", fields.join("\n"));
+            return Ok((frontmatter, text[offset..].trim_start_matches('\n')));
+        }
+        if !replaced.is_match(line) {
+            fields.push(line.trim_end_matches(['\r', '\n']).to_string());
+        }
+    }
+    Err(format!("Missing closing `{fence}` for the frontmatter"))
+}
+
+/// Splits the markdown on blank lines, keeping fenced code blocks, lists and
+/// blockquotes whole
+fn split_blocks(markdown: &str) -> Vec<Block> {
+    let fence = Regex::new(r"^ {0,3}(```|~~~)").unwrap();
+    let list_item = Regex::new(r"^ {0,3}([-*+]|\d{1,9}[.)])(\s|$)").unwrap();
+    let quote = Regex::new(r"^ {0,3}>").unwrap();
+    // a loose list goes on with another item or an indented paragraph, a
+    // blockquote with another `>` line
+    let continues = |first: &str, next: &str| {
+        if list_item.is_match(first) {
+            list_item.is_match(next) || next.starts_with("  ") || next.starts_with('\t')
+        } else {
+            quote.is_match(first) && quote.is_match(next)
+        }
+    };
+    let lines = markdown.lines().collect::<Vec<_>>();
+    let mut blocks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+    let mut flush = |current: &mut Vec<&str>, code: bool| {
+        if !current.is_empty() {
+            blocks.push(Block {
+                text: current.join("\n"),
+                code,
+            });
+            current.clear();
+        }
+    };
+    for (index, &line) in lines.iter().enumerate() {
+        if fence.is_match(line) {
+            if in_fence {
+                current.push(line);
+                flush(&mut current, true);
+            } else {
+                flush(&mut current, false);
+                current.push(line);
+            }
+            in_fence = !in_fence;
+        } else if in_fence {
+            current.push(line);
+        } else if line.trim().is_empty() {
+            let next = lines[index + 1..]
+                .iter()
+                .find(|line| !line.trim().is_empty());
+            match (current.first(), next) {
+                (Some(first), Some(next)) if continues(first, next) => {
+                    // keep a single blank line between the paragraphs
+                    if current.last().is_some_and(|last| !last.is_empty()) {
+                        current.push("");
+                    }
+                }
+                _ => flush(&mut current, false),
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    // an unclosed fence runs to the end of the file
+    flush(&mut current, in_fence);
+    blocks
+}
+
+/// Replaces the text of every block that is not code with its DeepL
+/// translation to `lang`
+fn deepl_translate(blocks: &mut [Block], key: &str, lang: &str) -> Result<(), String> {
+    // free API keys end with `:fx` and have their own endpoint
+    let url = if key.ends_with(":fx") {
+        "https://api-free.deepl.com/v2/translate"
+    } else {
+        "https://api.deepl.com/v2/translate"
+    };
+    let mut texts = blocks
+        .iter_mut()
+        .filter(|block| !block.code)
+        .collect::<Vec<_>>();
+    for batch in texts.chunks_mut(DEEPL_BATCH) {
+        let request = json!({
+            "text": batch.iter().map(|block| &block.text).collect::<Vec<_>>(),
+            "target_lang": lang.to_uppercase(),
+            "preserve_formatting": true,
+        });
+        let body = match ureq::post(url)
+            .timeout(Duration::from_secs(60))
+            .set("Authorization", &format!("DeepL-Auth-Key {key}"))
+            .set("Content-Type", "application/json")
+            .send_string(&request.to_string())
+        {
+            Ok(response) => response.into_string().map_err(|e| e.to_string())?,
+            Err(ureq::Error::Status(code, response)) => {
+                return Err(format!(
+                    "{url} returned {code}: {}",
+                    response.into_string().unwrap_or_default().trim()
+                ));
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let response: serde_json::Value =
+            serde_json::from_str(&body).map_err(|e| format!("invalid response, {e}"))?;
+        let translations = response["translations"]
+            .as_array()
+            .filter(|translations| translations.len() == batch.len())
+            .ok_or("unexpected response, the translations do not match the texts")?;
+        for (block, translation) in batch.iter_mut().zip(translations) {
+            if let Some(text) = translation["text"].as_str() {
+                block.text = text.to_string();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(markdown: &str) -> Vec<String> {
+        split_blocks(markdown)
+            .into_iter()
+            .map(|block| block.text)
+            .collect()
+    }
+
+    #[test]
+    fn paragraphs_are_blocks() {
+        assert_eq!(texts("One\nline\n\nTwo\n"), ["One\nline", "Two"]);
+    }
+
+    #[test]
+    fn loose_list_is_one_block() {
+        let markdown = "- one\n\n- two\n\n  more about two\n\nAfter\n";
+        assert_eq!(
+            texts(markdown),
+            ["- one\n\n- two\n\n  more about two", "After"]
+        );
+    }
+
+    #[test]
+    fn blockquote_paragraphs_are_one_block() {
+        let markdown = "> first\n\n> second\n\nAfter\n";
+        assert_eq!(texts(markdown), ["> first\n\n> second", "After"]);
+    }
+
+    #[test]
+    fn code_blocks_are_kept_whole() {
+        let blocks = split_blocks("Text\n```\na\n\nb\n```\n");
+        assert_eq!(blocks.len(), 2);
+        assert!(!blocks[0].code);
+        assert!(blocks[1].code);
+        assert_eq!(blocks[1].text, "```\na\n\nb\n```");
+    }
+}
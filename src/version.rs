@@ -0,0 +1,86 @@
+use log::{debug, error};
+use semver::Version;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+const CRATE_URL: &str = "https://crates.io/api/v1/crates/marmite";
+/// How long the latest version read from crates.io is reused
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Prints whether a newer marmite than this one is published on crates.io
+/// (`--version-check`).
+pub fn check_version() {
+    let latest = match cached_version().or_else(fetch_latest_version) {
+        Some(latest) => latest,
+        None => {
+            error!("Unable to check the latest marmite version on crates.io");
+            return;
+        }
+    };
+    let current = Version::parse(env!("CARGO_PKG_VERSION")).expect("Invalid package version");
+    match Version::parse(&latest) {
+        Ok(latest) if latest > current => println!(
+            "A newer version {latest} is available. Run `cargo install marmite` to upgrade."
+        ),
+        Ok(_) => println!("You are on the latest version ({current})"),
+        Err(e) => error!("Invalid version '{}' from crates.io: {}", latest, e),
+    }
+}
+
+/// `~/.cache/marmite/version-check`, or under `$XDG_CACHE_HOME` when set
+fn cache_path() -> Option<PathBuf> {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_dir.join("marmite").join("version-check"))
+}
+
+fn cached_version() -> Option<String> {
+    let path = cache_path()?;
+    let age = fs::metadata(&path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())?;
+    if age > CACHE_TTL {
+        return None;
+    }
+    let version = fs::read_to_string(&path).ok()?;
+    debug!("Using the latest version cached at {}", path.display());
+    Some(version.trim().to_string())
+}
+
+fn fetch_latest_version() -> Option<String> {
+    // crates.io asks API clients to identify themselves
+    let user_agent = format!(
+        "marmite/{} ({})",
+        env!("CARGO_PKG_VERSION"),
+        env!("CARGO_PKG_REPOSITORY")
+    );
+    let response = ureq::get(CRATE_URL)
+        .set("User-Agent", &user_agent)
+        .timeout(Duration::from_secs(10))
+        .call()
+        .map_err(|e| error!("Failed to fetch {}: {}", CRATE_URL, e))
+        .ok()?;
+    let body = response.into_string().ok()?;
+    let json: serde_json::Value = serde_json::from_str(&body)
+        .map_err(|e| error!("Invalid response from {}: {}", CRATE_URL, e))
+        .ok()?;
+    let latest = json["crate"]["max_stable_version"].as_str()?.to_string();
+
+    if let Some(path) = cache_path() {
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&path, &latest));
+        if let Err(e) = written {
+            debug!(
+                "Unable to cache the latest version at {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+    Some(latest)
+}